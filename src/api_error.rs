@@ -0,0 +1,183 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::broadcaster::BroadcastRecord;
+
+/// Machine readable error codes, returned in the `code` field of error responses
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The request body or parameters are not valid
+    InvalidRequest,
+    /// The client_id is not known to the service
+    UnknownClient,
+    /// A client with the client_id already exists
+    ClientExists,
+    /// The client configuration (key, address or public key) is not valid
+    InvalidClientConfig,
+    /// The client is watch-only, so is unable to fund transactions
+    WatchOnlyClient,
+    /// The client has insufficient balance for the request
+    InsufficientFunds,
+    /// The funding transaction was not accepted by the blockchain
+    BroadcastFailed,
+    /// The service is unable to connect to the blockchain
+    BlockchainUnavailable,
+    /// The service was unable to generate a key for the client
+    KeyGenerationFailed,
+    /// No valid token was provided
+    Unauthorized,
+    /// The token is not authorized for the request
+    Forbidden,
+}
+
+impl ErrorCode {
+    /// Return the HTTP status associated with the error code
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::InvalidClientConfig
+            | ErrorCode::WatchOnlyClient => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::UnknownClient => StatusCode::NOT_FOUND,
+            ErrorCode::ClientExists | ErrorCode::InsufficientFunds => StatusCode::CONFLICT,
+            ErrorCode::BroadcastFailed => StatusCode::BAD_GATEWAY,
+            ErrorCode::BlockchainUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::KeyGenerationFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// Return true if the request may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::BroadcastFailed | ErrorCode::BlockchainUnavailable
+        )
+    }
+}
+
+/// REST API error response
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub description: String,
+    pub retryable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Underlying error, for example why a client configuration is not valid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Per endpoint broadcast results, if broadcast fan-out is configured
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub broadcasts: Vec<BroadcastRecord>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, description: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            description: description.into(),
+            retryable: code.is_retryable(),
+            client_id: None,
+            error: None,
+            broadcasts: Vec::new(),
+        }
+    }
+
+    pub fn with_client_id(mut self, client_id: &str) -> Self {
+        self.client_id = Some(client_id.to_string());
+        self
+    }
+
+    pub fn with_error(mut self, error: impl Into<String>) -> Self {
+        self.error = Some(error.into());
+        self
+    }
+
+    pub fn with_broadcasts(mut self, broadcasts: Vec<BroadcastRecord>) -> Self {
+        self.broadcasts = broadcasts;
+        self
+    }
+
+    /// Unknown client_id error
+    pub fn unknown_client(client_id: &str) -> Self {
+        ApiError::new(
+            ErrorCode::UnknownClient,
+            format!("Unknown client_id {}", client_id),
+        )
+        .with_client_id(client_id)
+    }
+
+    /// Watch-only client error, watch-only clients are unable to fund transactions
+    pub fn watch_only_client(client_id: &str) -> Self {
+        ApiError::new(
+            ErrorCode::WatchOnlyClient,
+            format!("Client {client_id} is watch-only and unable to fund transactions"),
+        )
+        .with_client_id(client_id)
+    }
+
+    /// Insufficient client balance error
+    pub fn insufficient_funds(client_id: &str) -> Self {
+        ApiError::new(
+            ErrorCode::InsufficientFunds,
+            "Insufficent client balance to create funding transactions.",
+        )
+        .with_client_id(client_id)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} - {}", self.code, self.description)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self.code {
+            ErrorCode::BlockchainUnavailable => {
+                response.insert_header(("Retry-After", "10"));
+            }
+            ErrorCode::Unauthorized => {
+                response.insert_header(("WWW-Authenticate", "Bearer"));
+            }
+            _ => {}
+        }
+        response.json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_response() {
+        let error = ApiError::unknown_client("id1");
+        assert_eq!(error.error_response().status(), StatusCode::NOT_FOUND);
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "UNKNOWN_CLIENT");
+        assert_eq!(json["client_id"], "id1");
+        assert_eq!(json["retryable"], false);
+        assert!(json.get("broadcasts").is_none());
+
+        let error = ApiError::new(ErrorCode::BroadcastFailed, "Failed to broadcast");
+        assert_eq!(error.error_response().status(), StatusCode::BAD_GATEWAY);
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "BROADCAST_FAILED");
+        assert_eq!(json["retryable"], true);
+
+        let error = ApiError::new(ErrorCode::InsufficientFunds, "Insufficient funds");
+        assert_eq!(error.error_response().status(), StatusCode::CONFLICT);
+    }
+}
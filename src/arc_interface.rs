@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result},
+};
+
+use crate::{config::ArcConfig, util::tx_as_hexstr};
+
+/// ARC transaction status, as returned in txStatus
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ArcTxStatus {
+    Queued,
+    Received,
+    Stored,
+    AnnouncedToNetwork,
+    RequestedByNetwork,
+    SentToNetwork,
+    AcceptedByNetwork,
+    SeenOnNetwork,
+    SeenInOrphanMempool,
+    DoubleSpendAttempted,
+    MinedInStaleBlock,
+    Mined,
+    Confirmed,
+    Rejected,
+    #[serde(other)]
+    Unknown,
+}
+
+impl ArcTxStatus {
+    /// Return true if the status indicates that the tx has been rejected
+    /// Other statuses, including those not recognised, mean the tx has been accepted by ARC
+    pub fn is_failure(&self) -> bool {
+        matches!(self, ArcTxStatus::Rejected)
+    }
+
+    /// Return true if the tx has been accepted, but its status should be logged as a warning
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            ArcTxStatus::DoubleSpendAttempted
+                | ArcTxStatus::SeenInOrphanMempool
+                | ArcTxStatus::MinedInStaleBlock
+                | ArcTxStatus::Unknown
+        )
+    }
+}
+
+/// ARC transaction response, also used for ARC status callbacks
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArcTxResponse {
+    pub txid: Option<String>,
+    pub tx_status: Option<ArcTxStatus>,
+    pub block_hash: Option<String>,
+    pub block_height: Option<u64>,
+    pub extra_info: Option<String>,
+    /// Error responses provide a title and detail
+    pub title: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// Blockchain interface that submits transactions to an ARC endpoint
+/// Note that ARC only supports broadcasting, balance and UTXO queries are not supported
+pub struct ArcInterface {
+    url: String,
+    api_key: Option<String>,
+    callback_url: Option<String>,
+    callback_token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl ArcInterface {
+    pub fn new(url: &str, arc_config: &ArcConfig) -> Self {
+        ArcInterface {
+            url: url.trim_end_matches('/').to_string(),
+            api_key: arc_config.api_key.clone(),
+            callback_url: arc_config.callback_url.clone(),
+            callback_token: arc_config.callback_token.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn unsupported<T>(operation: &str) -> Result<T> {
+        Err(Error::InvalidOperation(format!(
+            "{} is not supported by the ARC interface",
+            operation
+        )))
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for ArcInterface {
+    fn set_network(&mut self, _network: &Network) {
+        // The network is determined by the ARC endpoint
+    }
+
+    async fn status(&self) -> Result<()> {
+        let response = self
+            .client
+            .get(format!("{}/v1/health", self.url))
+            .send()
+            .await
+            .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::IOError(std::io::Error::other(format!(
+                "ARC health status {}",
+                response.status()
+            ))))
+        }
+    }
+
+    async fn get_balance(&self, _address: &str) -> Result<Balance> {
+        ArcInterface::unsupported("get_balance")
+    }
+
+    async fn get_utxo(&self, _address: &str) -> Result<Utxo> {
+        ArcInterface::unsupported("get_utxo")
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        let mut request = self
+            .client
+            .post(format!("{}/v1/tx", self.url))
+            .json(&serde_json::json!({ "rawTx": tx_as_hexstr(tx) }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        if let Some(callback_url) = &self.callback_url {
+            request = request.header("X-CallbackUrl", callback_url);
+        }
+        if let Some(callback_token) = &self.callback_token {
+            request = request.header("X-CallbackToken", callback_token);
+        }
+        // Transport and server errors are reported as IOError, so that the tx is not treated as rejected.
+        // As are authorisation failures, timeouts and rate limits, as they are not a rejection of the tx
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+        let http_status = response.status();
+        if http_status.is_server_error()
+            || matches!(
+                http_status,
+                StatusCode::UNAUTHORIZED
+                    | StatusCode::FORBIDDEN
+                    | StatusCode::REQUEST_TIMEOUT
+                    | StatusCode::TOO_MANY_REQUESTS
+            )
+        {
+            return Err(Error::IOError(std::io::Error::other(format!(
+                "ARC broadcast failed - status {}",
+                http_status
+            ))));
+        }
+        let arc_response: ArcTxResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+        log::debug!("ARC broadcast response {:?}", &arc_response);
+
+        match arc_response.tx_status {
+            Some(tx_status) if http_status.is_success() && !tx_status.is_failure() => {
+                Ok(arc_response.txid.unwrap_or_else(|| tx.hash().encode()))
+            }
+            tx_status => Err(Error::BadData(format!(
+                "ARC broadcast failed - status {}, txStatus {:?}, {}",
+                http_status,
+                tx_status,
+                arc_response
+                    .extra_info
+                    .or(arc_response.detail)
+                    .or(arc_response.title)
+                    .unwrap_or_default()
+            ))),
+        }
+    }
+
+    async fn get_tx(&self, _txid: &str) -> Result<Tx> {
+        ArcInterface::unsupported("get_tx")
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        ArcInterface::unsupported("get_block_headers")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{post, App, HttpResponse, HttpServer};
+    use chain_gang::{
+        messages::{OutPoint, TxIn},
+        script::Script,
+        util::Hash256,
+    };
+
+    #[post("/v1/tx")]
+    async fn mock_arc_tx(body: String) -> HttpResponse {
+        // Reject txs with no inputs, accept the rest
+        let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let raw_tx = request["rawTx"].as_str().unwrap();
+        if raw_tx.starts_with("0100000000") {
+            HttpResponse::Ok().body(
+                r#"{"txid": "00", "txStatus": "REJECTED", "extraInfo": "no inputs", "status": 200}"#,
+            )
+        } else if raw_tx.starts_with("03") {
+            HttpResponse::Unauthorized().body(r#"{"title": "Unauthorized", "status": 401}"#)
+        } else if raw_tx.starts_with("02") {
+            HttpResponse::Ok()
+                .body(r#"{"txid": "5678", "txStatus": "DOUBLE_SPEND_ATTEMPTED", "status": 200}"#)
+        } else {
+            HttpResponse::Ok()
+                .body(r#"{"txid": "1234", "txStatus": "SEEN_ON_NETWORK", "status": 200}"#)
+        }
+    }
+
+    /// Start a mock ARC server, returning its url
+    fn start_mock_arc() -> String {
+        let server = HttpServer::new(|| App::new().service(mock_arc_tx))
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        format!("http://{}", addr)
+    }
+
+    #[actix_web::test]
+    async fn test_arc_broadcast() {
+        let url = start_mock_arc();
+        let interface = ArcInterface::new(&url, &ArcConfig::default());
+
+        // Rejected
+        let mut tx = Tx {
+            version: 1,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            lock_time: 0,
+        };
+        assert!(matches!(
+            interface.broadcast_tx(&tx).await,
+            Err(Error::BadData(_))
+        ));
+
+        // Seen on network
+        tx.inputs.push(TxIn {
+            prev_output: OutPoint {
+                hash: Hash256([0; 32]),
+                index: 0,
+            },
+            unlock_script: Script::new(),
+            sequence: 0xffffffff,
+        });
+        assert_eq!(interface.broadcast_tx(&tx).await.unwrap(), "1234");
+
+        // Double spend attempted is accepted by ARC, so is not a failure
+        tx.version = 2;
+        assert_eq!(interface.broadcast_tx(&tx).await.unwrap(), "5678");
+
+        // Authorisation failures are not a rejection of the tx
+        tx.version = 3;
+        assert!(matches!(
+            interface.broadcast_tx(&tx).await,
+            Err(Error::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn test_arc_tx_status() {
+        let response: ArcTxResponse =
+            serde_json::from_str(r#"{"txid": "1234", "txStatus": "MINED", "blockHeight": 10}"#)
+                .unwrap();
+        assert_eq!(response.tx_status, Some(ArcTxStatus::Mined));
+        let response: ArcTxResponse =
+            serde_json::from_str(r#"{"txStatus": "SOMETHING_NEW"}"#).unwrap();
+        assert_eq!(response.tx_status, Some(ArcTxStatus::Unknown));
+        assert!(!ArcTxStatus::Unknown.is_failure());
+        let response: ArcTxResponse =
+            serde_json::from_str(r#"{"txStatus": "MINED_IN_STALE_BLOCK"}"#).unwrap();
+        assert_eq!(response.tx_status, Some(ArcTxStatus::MinedInStaleBlock));
+        assert!(ArcTxStatus::MinedInStaleBlock.is_warning());
+        assert!(ArcTxStatus::Rejected.is_failure());
+    }
+}
@@ -0,0 +1,57 @@
+use actix_web::HttpRequest;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::secret::SecretString;
+
+/// Return the SHA-256 hash of the token as a hex string, tokens are only stored as hashes
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Generate a new random API key
+pub fn generate_api_key() -> SecretString {
+    let mut key_bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut key_bytes);
+    SecretString::from(hex::encode(key_bytes))
+}
+
+/// Return the bearer token from the request's Authorization header
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_hash_token() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let api_key = generate_api_key();
+        assert_eq!(api_key.expose_secret().len(), 64);
+        assert_ne!(hash_token(api_key.expose_secret()), api_key.expose_secret());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let req = TestRequest::default()
+            .insert_header(("Authorization", "Bearer token1"))
+            .to_http_request();
+        assert_eq!(bearer_token(&req), Some("token1"));
+        let req = TestRequest::default()
+            .insert_header(("Authorization", "Basic token1"))
+            .to_http_request();
+        assert_eq!(bearer_token(&req), None);
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(bearer_token(&req), None);
+    }
+}
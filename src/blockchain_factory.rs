@@ -1,35 +1,302 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use chain_gang::interface::{BlockchainInterface, TestInterface, UaaSInterface, WocInterface};
 
-use crate::config::Config;
+use crate::{
+    arc_interface::ArcInterface,
+    broadcaster::Broadcaster,
+    caching_interface::{CachingInterface, SharedAddressCache},
+    chaos_interface::ChaosInterface,
+    circuit_breaker::{CircuitBreaker, CircuitBreakerInterface},
+    config::{CircuitBreakerConfig, Config},
+    failover_interface::{ActiveBackend, FailoverInterface},
+    http_error_interface::HttpErrorInterface,
+    recording_interface::{RecordingInterface, ReplayInterface},
+    rpc_interface::RpcInterface,
+    simulated_interface::SimulatedInterface,
+};
 
-/// Takes a config and returns the appropriate configured object that implements BlockchainInterface
-pub fn blockchain_factory(config: &Config) -> Box<dyn BlockchainInterface + Send + Sync> {
-    match config.blockchain_interface.interface_type.as_str() {
+/// Return the configured object for the given interface type
+fn interface_factory(
+    config: &Config,
+    interface_type: &str,
+    url: &Option<String>,
+) -> Box<dyn BlockchainInterface + Send + Sync> {
+    let network = &config.get_network().unwrap();
+    match interface_type {
         "woc" => {
             let mut interface = WocInterface::new();
-            interface.set_network(&config.get_network().unwrap());
-            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+            interface.set_network(network);
+            Box::new(HttpErrorInterface::new(Box::new(interface)))
+                as Box<dyn BlockchainInterface + Send + Sync>
         }
         "test" => {
             let mut interface = TestInterface::new();
-            interface.set_network(&config.get_network().unwrap());
+            interface.set_network(network);
             Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
         }
         "uaas" => {
-            if let Some(uaas_url) = &config.blockchain_interface.url {
+            if let Some(uaas_url) = url {
                 let mut interface = UaaSInterface::new(uaas_url).unwrap();
-                interface.set_network(&config.get_network().unwrap());
+                interface.set_network(network);
+                Box::new(HttpErrorInterface::new(Box::new(interface)))
+                    as Box<dyn BlockchainInterface + Send + Sync>
+            } else {
+                panic!("Config blockchain interface url not found.");
+            }
+        }
+        "arc" => {
+            if let Some(arc_url) = url {
+                let interface = ArcInterface::new(arc_url, &config.arc);
                 Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
             } else {
                 panic!("Config blockchain interface url not found.");
             }
         }
+        "rpc" => {
+            if let Some(rpc_url) = url {
+                let interface = RpcInterface::new(rpc_url, &config.rpc);
+                Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+            } else {
+                panic!("Config blockchain interface url not found.");
+            }
+        }
+        "record" => {
+            let recording = match &config.recording {
+                Some(recording) => recording,
+                None => panic!("Config recording not found."),
+            };
+            let recorded_type = match &recording.interface_type {
+                Some(recorded_type) if recorded_type != "record" => recorded_type,
+                _ => panic!("Config recording interface_type not found."),
+            };
+            check_query_interface(recorded_type);
+            let recorded = interface_factory(config, recorded_type, url);
+            let interface = RecordingInterface::new(recorded, &recording.fixture)
+                .unwrap_or_else(|e| panic!("Unable to open recording fixture {:?}", e));
+            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+        }
+        "replay" => {
+            let recording = match &config.recording {
+                Some(recording) => recording,
+                None => panic!("Config recording not found."),
+            };
+            let interface = ReplayInterface::new(&recording.fixture)
+                .unwrap_or_else(|e| panic!("Unable to read recording fixture {:?}", e));
+            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+        }
+        "simulated" => {
+            let interface = SimulatedInterface::from_config(*network, &config.simulated);
+            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+        }
 
         _ => {
-            panic!(
-                "Unknown interface type '{}'",
-                config.blockchain_interface.interface_type
-            );
+            panic!("Unknown interface type '{}'", interface_type);
         }
     }
 }
+
+/// Panic if the interface type is broadcast only, so can not be used for balance and UTXO queries
+fn check_query_interface(interface_type: &str) {
+    if interface_type == "arc" {
+        panic!(
+            "Config interface_type '{}' only supports broadcasting, use it in [[broadcast.broadcaster]].",
+            interface_type
+        );
+    }
+}
+
+/// Wrap the interface with its own circuit breaker and the configured timeouts,
+/// so that a hung or failing endpoint returns an IOError rather than blocking the caller
+fn circuit_breaker_factory(
+    config: &Config,
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+) -> Box<dyn BlockchainInterface + Send + Sync> {
+    Box::new(CircuitBreakerInterface::new(
+        interface,
+        &config.circuit_breaker,
+        Arc::new(Mutex::new(CircuitBreaker::new(&config.circuit_breaker))),
+    ))
+}
+
+/// Takes a config and returns a Broadcaster, if broadcast fan-out is configured
+/// Each broadcaster has its own circuit breaker and the configured broadcast_timeout
+pub fn broadcaster_factory(config: &Config) -> Option<Broadcaster> {
+    let broadcast_config = config.broadcast.as_ref()?;
+    if broadcast_config.quorum == 0 || broadcast_config.quorum > broadcast_config.broadcaster.len()
+    {
+        panic!(
+            "Config broadcast quorum {} is not valid for {} broadcasters.",
+            broadcast_config.quorum,
+            broadcast_config.broadcaster.len()
+        );
+    }
+    let mut broadcaster = Broadcaster::new(broadcast_config.quorum);
+    for endpoint in &broadcast_config.broadcaster {
+        let interface = circuit_breaker_factory(
+            config,
+            interface_factory(config, &endpoint.interface_type, &endpoint.url),
+        );
+        broadcaster.add_endpoint(&endpoint.get_name(), interface);
+    }
+    Some(broadcaster)
+}
+
+/// Return the circuit breaker config for the interface returned by blockchain_factory
+/// Each failover backend has its own timeouts, so the timeouts allow for every backend to be tried in turn
+pub fn circuit_breaker_config(config: &Config) -> CircuitBreakerConfig {
+    let mut breaker_config = config.circuit_breaker.clone();
+    let interface_config = &config.blockchain_interface;
+    if interface_config.interface_type == "failover" {
+        let no_of_backends = interface_config.backend.as_ref().map_or(1, |b| b.len()) as u64;
+        breaker_config.query_timeout = breaker_config.query_timeout * no_of_backends + 1;
+        breaker_config.broadcast_timeout = breaker_config.broadcast_timeout * no_of_backends + 1;
+    }
+    breaker_config
+}
+
+/// Takes a config and returns the appropriate configured object that implements BlockchainInterface
+/// The active_backend is updated with the name of the backend in use,
+/// the address_cache is used to cache balances and UTXOs if caching is configured
+pub fn blockchain_factory(
+    config: &Config,
+    active_backend: &ActiveBackend,
+    address_cache: &SharedAddressCache,
+) -> Box<dyn BlockchainInterface + Send + Sync> {
+    let mut interface = backend_factory(config, active_backend);
+    if let Some(chaos_config) = &config.chaos {
+        log::warn!("Chaos fault injection is enabled");
+        interface = Box::new(ChaosInterface::new(interface, chaos_config));
+    }
+    match &config.caching {
+        Some(caching_config) => {
+            let interface = CachingInterface::new(interface, caching_config, address_cache)
+                .unwrap_or_else(|e| panic!("{}", e));
+            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+        }
+        None => interface,
+    }
+}
+
+/// Return the configured interface, which may be a failover interface across multiple backends
+/// Each failover backend has its own circuit breaker and timeouts, so that a hung backend is failed over
+fn backend_factory(
+    config: &Config,
+    active_backend: &ActiveBackend,
+) -> Box<dyn BlockchainInterface + Send + Sync> {
+    let interface_config = &config.blockchain_interface;
+
+    match interface_config.interface_type.as_str() {
+        "failover" => {
+            let backends = match &interface_config.backend {
+                Some(backends) if !backends.is_empty() => backends,
+                _ => panic!("Config blockchain interface backend not found."),
+            };
+            let cool_down_period = Duration::from_secs(interface_config.cool_down_period);
+            let mut interface = FailoverInterface::new(cool_down_period, active_backend.clone());
+            for backend in backends {
+                check_query_interface(&backend.interface_type);
+                interface.add_backend(
+                    &backend.get_name(),
+                    circuit_breaker_factory(
+                        config,
+                        interface_factory(config, &backend.interface_type, &backend.url),
+                    ),
+                );
+            }
+            Box::new(interface) as Box<dyn BlockchainInterface + Send + Sync>
+        }
+        interface_type => {
+            check_query_interface(interface_type);
+            *active_backend.lock().unwrap() = Some(interface_type.to_string());
+            interface_factory(config, interface_type, &interface_config.url)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use chain_gang::network::Network;
+
+    use crate::config::{BlockchainBackendConfig, ChaosConfig};
+
+    #[test]
+    #[should_panic(expected = "only supports broadcasting")]
+    fn test_arc_primary_interface() {
+        let mut config = Config::default();
+        config.blockchain_interface.interface_type = "arc".to_string();
+        config.blockchain_interface.network_type = "testnet".to_string();
+        config.blockchain_interface.url = Some("http://127.0.0.1:9090".to_string());
+        blockchain_factory(
+            &config,
+            &ActiveBackend::default(),
+            &SharedAddressCache::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "only supports broadcasting")]
+    fn test_arc_failover_backend() {
+        let mut config = Config::default();
+        config.blockchain_interface.interface_type = "failover".to_string();
+        config.blockchain_interface.network_type = "testnet".to_string();
+        config.blockchain_interface.backend = Some(vec![
+            BlockchainBackendConfig {
+                interface_type: "simulated".to_string(),
+                url: None,
+                ..Default::default()
+            },
+            BlockchainBackendConfig {
+                interface_type: "arc".to_string(),
+                url: Some("http://127.0.0.1:9090".to_string()),
+                ..Default::default()
+            },
+        ]);
+        blockchain_factory(
+            &config,
+            &ActiveBackend::default(),
+            &SharedAddressCache::default(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failover_slow_primary() {
+        let mut config = Config::default();
+        config.circuit_breaker.query_timeout = 1;
+        // The slow primary takes longer than the query_timeout to respond
+        let slow = ChaosInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &ChaosConfig {
+                latency_probability: 1.0,
+                latency_ms: 60_000,
+                ..Default::default()
+            },
+        );
+        let active_backend = ActiveBackend::default();
+        let mut interface = FailoverInterface::new(Duration::from_secs(60), active_backend.clone());
+        interface.add_backend("slow", circuit_breaker_factory(&config, Box::new(slow)));
+        interface.add_backend(
+            "simulated",
+            circuit_breaker_factory(
+                &config,
+                Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            ),
+        );
+
+        let start = Instant::now();
+        assert!(interface.status().await.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(active_backend.lock().unwrap().as_deref(), Some("simulated"));
+
+        // The slow primary is cooling down, so the secondary is tried first
+        let start = Instant::now();
+        assert!(interface.status().await.is_ok());
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
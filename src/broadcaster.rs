@@ -0,0 +1,222 @@
+use futures::future::join_all;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use chain_gang::{interface::BlockchainInterface, messages::Tx};
+
+/// The result of broadcasting a tx to one endpoint
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct EndpointResult {
+    pub endpoint: String,
+    pub accepted: bool,
+    /// The endpoint's response or error
+    pub detail: String,
+}
+
+/// The result of broadcasting a tx to all the endpoints
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct BroadcastRecord {
+    pub tx_hash: String,
+    /// True if the quorum of endpoints accepted the tx
+    pub accepted: bool,
+    pub endpoints: Vec<EndpointResult>,
+}
+
+/// Broadcasts txs to multiple endpoints in parallel
+pub struct Broadcaster {
+    endpoints: Vec<(String, Box<dyn BlockchainInterface + Send + Sync>)>,
+    /// Number of endpoints that must accept a tx for the broadcast to succeed
+    quorum: usize,
+}
+
+impl Broadcaster {
+    pub fn new(quorum: usize) -> Self {
+        Broadcaster {
+            endpoints: Vec::new(),
+            quorum,
+        }
+    }
+
+    pub fn add_endpoint(
+        &mut self,
+        name: &str,
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+    ) {
+        self.endpoints.push((name.to_string(), interface));
+    }
+
+    /// Broadcast the tx to all the endpoints, recording each endpoint's result
+    pub async fn broadcast(&self, tx: &Tx) -> BroadcastRecord {
+        let results = join_all(
+            self.endpoints
+                .iter()
+                .map(|(_, interface)| interface.broadcast_tx(tx)),
+        )
+        .await;
+
+        let endpoints: Vec<EndpointResult> = self
+            .endpoints
+            .iter()
+            .zip(results)
+            .map(|((name, _), result)| match result {
+                Ok(detail) => EndpointResult {
+                    endpoint: name.clone(),
+                    accepted: true,
+                    detail,
+                },
+                Err(e) => EndpointResult {
+                    endpoint: name.clone(),
+                    accepted: false,
+                    detail: format!("{:?}", e),
+                },
+            })
+            .collect();
+
+        let no_accepted = endpoints.iter().filter(|e| e.accepted).count();
+        let record = BroadcastRecord {
+            tx_hash: tx.hash().encode(),
+            accepted: no_accepted >= self.quorum,
+            endpoints,
+        };
+        log::info!(
+            "broadcast {} accepted by {} of {} endpoints",
+            record.tx_hash,
+            no_accepted,
+            record.endpoints.len()
+        );
+        for endpoint in record.endpoints.iter().filter(|e| !e.accepted) {
+            log::warn!(
+                "broadcast {} rejected by {} - {}",
+                record.tx_hash,
+                endpoint.endpoint,
+                endpoint.detail
+            );
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use chain_gang::network::Network;
+
+    use crate::{
+        chaos_interface::ChaosInterface,
+        circuit_breaker::{CircuitBreaker, CircuitBreakerInterface},
+        config::{ChaosConfig, CircuitBreakerConfig},
+        simulated_interface::SimulatedInterface,
+    };
+
+    #[tokio::test]
+    async fn test_broadcast_timeout() {
+        let config = CircuitBreakerConfig {
+            broadcast_timeout: 1,
+            ..Default::default()
+        };
+        // The hung endpoint takes longer than the broadcast_timeout to respond
+        let hung = ChaosInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &ChaosConfig {
+                latency_probability: 1.0,
+                latency_ms: 60_000,
+                ..Default::default()
+            },
+        );
+        let mut broadcaster = Broadcaster::new(1);
+        for (name, interface) in [
+            (
+                "hung",
+                Box::new(hung) as Box<dyn BlockchainInterface + Send + Sync>,
+            ),
+            (
+                "simulated",
+                Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            ),
+        ] {
+            let interface = CircuitBreakerInterface::new(
+                interface,
+                &config,
+                Arc::new(Mutex::new(CircuitBreaker::new(&config))),
+            );
+            broadcaster.add_endpoint(name, Box::new(interface));
+        }
+
+        let start = Instant::now();
+        let record = broadcaster.broadcast(&Tx::default()).await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!record.endpoints[0].accepted);
+        assert!(record.endpoints[0].detail.contains("timed out"));
+    }
+
+    /// Return a broadcaster with endpoints that accept, reject and fail to receive the tx
+    fn test_broadcaster(quorum: usize) -> Broadcaster {
+        let chaos = |config: ChaosConfig| {
+            Box::new(ChaosInterface::new(
+                Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+                &config,
+            ))
+        };
+        let accepting = || {
+            chaos(ChaosConfig {
+                drop_probability: 1.0,
+                ..Default::default()
+            })
+        };
+        let mut broadcaster = Broadcaster::new(quorum);
+        broadcaster.add_endpoint("http://accepting1", accepting());
+        broadcaster.add_endpoint("http://accepting2", accepting());
+        // The invalid tx is rejected by the simulated chain
+        broadcaster.add_endpoint(
+            "http://rejecting",
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+        );
+        broadcaster.add_endpoint(
+            "http://unavailable",
+            chaos(ChaosConfig {
+                error_probability: 1.0,
+                ..Default::default()
+            }),
+        );
+        broadcaster
+    }
+
+    #[tokio::test]
+    async fn test_quorum_met() {
+        let tx = Tx::default();
+        let record = test_broadcaster(2).broadcast(&tx).await;
+        assert!(record.accepted);
+        assert_eq!(record.tx_hash, tx.hash().encode());
+
+        // Each endpoint's result is recorded, in the order the endpoints were added
+        let endpoints: Vec<(&str, bool)> = record
+            .endpoints
+            .iter()
+            .map(|e| (e.endpoint.as_str(), e.accepted))
+            .collect();
+        assert_eq!(
+            endpoints,
+            [
+                ("http://accepting1", true),
+                ("http://accepting2", true),
+                ("http://rejecting", false),
+                ("http://unavailable", false),
+            ]
+        );
+        assert_eq!(record.endpoints[0].detail, tx.hash().encode());
+        assert!(record.endpoints[2].detail.contains("BadData"));
+        assert!(record.endpoints[3].detail.contains("IOError"));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_missed() {
+        let record = test_broadcaster(3).broadcast(&Tx::default()).await;
+        assert!(!record.accepted);
+        assert_eq!(record.endpoints.iter().filter(|e| e.accepted).count(), 2);
+    }
+}
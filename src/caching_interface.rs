@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::Result,
+};
+
+use crate::config::CachingConfig;
+
+/// Token bucket rate limiter
+struct TokenBucket {
+    /// Maximum number of tokens, the size of a burst of requests
+    capacity: f64,
+    /// Tokens added per second
+    refill_rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(refill_rate: f64, capacity: u32) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        TokenBucket {
+            capacity,
+            refill_rate,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, returns the time to wait if none are available
+    fn try_take(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_rate,
+            ))
+        }
+    }
+}
+
+/// Cached response and the time it expires
+struct CacheEntry<T> {
+    value: T,
+    expires: Instant,
+}
+
+type InFlight = Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>;
+
+/// Lock used to coalesce concurrent requests for the same key,
+/// the key's entry is removed once the last request using it has finished
+struct RequestLock<'a> {
+    in_flight: &'a InFlight,
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for RequestLock<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // Only the map and this request hold the lock, so no requests are waiting on it
+        if Arc::strong_count(&self.lock) <= 2 {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// Cached balance and UTXOs of an address, fetched together so that they are consistent
+#[derive(Clone)]
+struct AddressEntry {
+    balance: Balance,
+    utxo: Utxo,
+}
+
+/// Cache of address balances and UTXOs, shared so that addresses can be invalidated
+/// when they are known to have changed
+#[derive(Default)]
+pub struct AddressCache {
+    entries: Mutex<HashMap<String, CacheEntry<AddressEntry>>>,
+}
+
+pub type SharedAddressCache = Arc<AddressCache>;
+
+impl AddressCache {
+    fn get(&self, address: &str) -> Option<AddressEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(address)
+            .filter(|entry| entry.expires > Instant::now())
+            .map(|entry| entry.value.clone())
+    }
+
+    fn insert(&self, address: &str, value: AddressEntry, ttl: Duration) {
+        self.entries.lock().unwrap().insert(
+            address.to_string(),
+            CacheEntry {
+                value,
+                expires: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// Remove the addresses from the cache, so that they are fetched on the next request
+    pub fn invalidate(&self, addresses: &[String]) {
+        let mut entries = self.entries.lock().unwrap();
+        for address in addresses {
+            entries.remove(address);
+        }
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Blockchain interface wrapper that rate limits requests to the wrapped interface
+/// and caches address (balance and UTXO) and block header responses.
+/// Concurrent requests for the same data are coalesced into one upstream request.
+/// A successful broadcast invalidates the cached addresses, as it changes their balances and UTXOs.
+/// Note that broadcasts are rate limited but never cached.
+pub struct CachingInterface {
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+    rate_limiter: Option<Mutex<TokenBucket>>,
+    balance_ttl: Duration,
+    header_ttl: Duration,
+    address_cache: SharedAddressCache,
+    header_cache: Mutex<Option<CacheEntry<String>>>,
+    /// Per request key locks, used to coalesce concurrent requests
+    in_flight: InFlight,
+}
+
+impl CachingInterface {
+    /// Returns an error if the config is not valid
+    pub fn new(
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+        caching_config: &CachingConfig,
+        address_cache: &SharedAddressCache,
+    ) -> std::result::Result<Self, String> {
+        if let Some(rate) = caching_config.requests_per_second {
+            if rate.is_nan() || rate <= 0.0 {
+                return Err(format!(
+                    "Config caching requests_per_second must be greater than 0, found {}",
+                    rate
+                ));
+            }
+        }
+        Ok(CachingInterface {
+            interface,
+            rate_limiter: caching_config
+                .requests_per_second
+                .map(|rate| Mutex::new(TokenBucket::new(rate, caching_config.burst))),
+            balance_ttl: Duration::from_secs(caching_config.balance_ttl),
+            header_ttl: Duration::from_secs(caching_config.header_ttl),
+            address_cache: address_cache.clone(),
+            header_cache: Mutex::new(None),
+            in_flight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Wait until the rate limiter allows a request
+    async fn rate_limit(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            loop {
+                let wait = rate_limiter.lock().unwrap().try_take();
+                match wait {
+                    Some(duration) => tokio::time::sleep(duration).await,
+                    None => return,
+                }
+            }
+        }
+    }
+
+    /// Return the lock for this request key
+    fn request_lock(&self, key: &str) -> RequestLock<'_> {
+        let lock = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        RequestLock {
+            in_flight: &self.in_flight,
+            key: key.to_string(),
+            lock,
+        }
+    }
+
+    /// Return the address's balance and UTXOs, from the cache if present
+    async fn get_address(&self, address: &str) -> Result<AddressEntry> {
+        if let Some(entry) = self.address_cache.get(address) {
+            return Ok(entry);
+        }
+        // Coalesce, concurrent requests wait here and then use the cached response
+        let request_lock = self.request_lock(&format!("address:{}", address));
+        let _guard = request_lock.lock.lock().await;
+        if let Some(entry) = self.address_cache.get(address) {
+            return Ok(entry);
+        }
+        self.rate_limit().await;
+        let balance = self.interface.get_balance(address).await?;
+        self.rate_limit().await;
+        let utxo = self.interface.get_utxo(address).await?;
+        let entry = AddressEntry { balance, utxo };
+        self.address_cache
+            .insert(address, entry.clone(), self.balance_ttl);
+        Ok(entry)
+    }
+
+    fn cached_header(&self) -> Option<String> {
+        let cache = self.header_cache.lock().unwrap();
+        cache
+            .as_ref()
+            .filter(|entry| entry.expires > Instant::now())
+            .map(|entry| entry.value.clone())
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for CachingInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.interface.set_network(network);
+    }
+
+    async fn status(&self) -> Result<()> {
+        self.rate_limit().await;
+        self.interface.status().await
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        if self.balance_ttl.is_zero() {
+            self.rate_limit().await;
+            return self.interface.get_balance(address).await;
+        }
+        Ok(self.get_address(address).await?.balance)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        if self.balance_ttl.is_zero() {
+            self.rate_limit().await;
+            return self.interface.get_utxo(address).await;
+        }
+        Ok(self.get_address(address).await?.utxo)
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        self.rate_limit().await;
+        let result = self.interface.broadcast_tx(tx).await;
+        if result.is_ok() {
+            self.address_cache.clear();
+        }
+        result
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        self.rate_limit().await;
+        self.interface.get_tx(txid).await
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        if let Some(headers) = self.cached_header() {
+            return Ok(headers);
+        }
+        let request_lock = self.request_lock("headers");
+        let _guard = request_lock.lock.lock().await;
+        if let Some(headers) = self.cached_header() {
+            return Ok(headers);
+        }
+        self.rate_limit().await;
+        let headers = self.interface.get_block_headers().await?;
+        if !self.header_ttl.is_zero() {
+            *self.header_cache.lock().unwrap() = Some(CacheEntry {
+                value: headers.clone(),
+                expires: Instant::now() + self.header_ttl,
+            });
+        }
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::{Client, FundRequest},
+        config::ClientConfig,
+        simulated_interface::SimulatedInterface,
+    };
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let caching_config = CachingConfig {
+            requests_per_second: Some(20.0),
+            burst: 2,
+            ..Default::default()
+        };
+        let interface = CachingInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &caching_config,
+            &SharedAddressCache::default(),
+        )
+        .unwrap();
+        // The first two requests are the burst, the next two are rate limited
+        let start = Instant::now();
+        for _ in 0..4 {
+            interface.status().await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[tokio::test]
+    async fn test_balance_cache() {
+        let network = Network::BSV_Testnet;
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+        let simulated = SimulatedInterface::new(network);
+        let caching_config = CachingConfig {
+            balance_ttl: 60,
+            ..Default::default()
+        };
+        let interface = CachingInterface::new(
+            Box::new(simulated.clone()),
+            &caching_config,
+            &SharedAddressCache::default(),
+        )
+        .unwrap();
+
+        assert_eq!(interface.get_balance(address).await.unwrap().unconfirmed, 0);
+        simulated.fund_address(address, 100_000).unwrap();
+        // Cached balance and UTXOs are returned until the ttl expires, so are consistent
+        assert_eq!(interface.get_balance(address).await.unwrap().unconfirmed, 0);
+        assert!(interface.get_utxo(address).await.unwrap().is_empty());
+
+        // A broadcast invalidates the cache
+        let client_config = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        let mut client = Client::new(&client_config, network).unwrap();
+        client.update_balance(&simulated).await.unwrap();
+        let fund_request = FundRequest {
+            client_id: "id1".to_string(),
+            satoshi: 123,
+            no_of_outpoints: 1,
+            multiple_tx: false,
+            locking_script: hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac")
+                .unwrap(),
+        };
+        let tx = client.create_funding_tx(&fund_request).unwrap();
+        interface.broadcast_tx(&tx).await.unwrap();
+        let balance = interface.get_balance(address).await.unwrap();
+        assert_eq!(balance.unconfirmed, 100_000 - 750);
+        let utxo = interface.get_utxo(address).await.unwrap();
+        let utxo_value: i64 = utxo.iter().map(|entry| entry.value).sum();
+        assert_eq!(utxo_value, balance.unconfirmed as i64);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_address() {
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let caching_config = CachingConfig {
+            balance_ttl: 60,
+            ..Default::default()
+        };
+        let address_cache = SharedAddressCache::default();
+        let interface =
+            CachingInterface::new(Box::new(simulated.clone()), &caching_config, &address_cache)
+                .unwrap();
+
+        assert_eq!(interface.get_balance(address).await.unwrap().unconfirmed, 0);
+        simulated.fund_address(address, 1000).unwrap();
+        address_cache.invalidate(&[address.to_string()]);
+        assert_eq!(
+            interface.get_balance(address).await.unwrap().unconfirmed,
+            1000
+        );
+        assert_eq!(interface.get_utxo(address).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rate() {
+        for rate in [0.0, -1.0] {
+            let caching_config = CachingConfig {
+                requests_per_second: Some(rate),
+                ..Default::default()
+            };
+            let simulated = Box::new(SimulatedInterface::new(Network::BSV_Testnet));
+            let address_cache = SharedAddressCache::default();
+            assert!(CachingInterface::new(simulated, &caching_config, &address_cache).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_locks_removed() {
+        let caching_config = CachingConfig {
+            header_ttl: 60,
+            ..Default::default()
+        };
+        let interface = CachingInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &caching_config,
+            &SharedAddressCache::default(),
+        )
+        .unwrap();
+        let (first, second) =
+            futures::future::join(interface.get_block_headers(), interface.get_block_headers())
+                .await;
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert!(interface.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result},
+};
+
+use crate::config::{ChaosConfig, ChaosFault};
+
+/// Blockchain interface wrapper that injects faults, used to test how the service handles failures.
+/// Faults are injected at random, at the configured probabilities, or at scripted call numbers.
+pub struct ChaosInterface {
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+    config: ChaosConfig,
+    rng: Mutex<StdRng>,
+    /// Number of calls made to each method
+    call_counts: Mutex<HashMap<&'static str, u64>>,
+    /// Last successful responses, returned as stale responses
+    last_balance: Mutex<HashMap<String, Balance>>,
+    last_utxo: Mutex<HashMap<String, Utxo>>,
+}
+
+impl ChaosInterface {
+    pub fn new(
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+        config: &ChaosConfig,
+    ) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        ChaosInterface {
+            interface,
+            config: config.clone(),
+            rng: Mutex::new(rng),
+            call_counts: Mutex::new(HashMap::new()),
+            last_balance: Mutex::new(HashMap::new()),
+            last_utxo: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count the call and return the faults to inject into it
+    fn faults(&self, method: &'static str) -> Vec<ChaosFault> {
+        let call = {
+            let mut call_counts = self.call_counts.lock().unwrap();
+            let count = call_counts.entry(method).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let mut faults: Vec<ChaosFault> = self
+            .config
+            .script
+            .iter()
+            .filter(|s| {
+                s.call == call && (s.method.is_none() || s.method.as_deref() == Some(method))
+            })
+            .map(|s| s.fault)
+            .collect();
+
+        let mut rng = self.rng.lock().unwrap();
+        let probabilities = [
+            (ChaosFault::Latency, self.config.latency_probability),
+            (ChaosFault::Error, self.config.error_probability),
+            (ChaosFault::Stale, self.config.stale_probability),
+            (ChaosFault::Drop, self.config.drop_probability),
+        ];
+        for (fault, probability) in probabilities {
+            if probability > 0.0 && rng.gen_bool(probability.min(1.0)) && !faults.contains(&fault) {
+                faults.push(fault);
+            }
+        }
+        faults
+    }
+
+    /// Inject the latency and error faults, common to all methods
+    async fn inject(&self, method: &'static str, faults: &[ChaosFault]) -> Result<()> {
+        if faults.contains(&ChaosFault::Latency) {
+            tokio::time::sleep(Duration::from_millis(self.config.latency_ms)).await;
+        }
+        if faults.contains(&ChaosFault::Error) {
+            log::info!("Chaos - injected error into {}", method);
+            // Injected errors are reported as transport errors
+            return Err(Error::IOError(std::io::Error::other(format!(
+                "Chaos - injected error into {}",
+                method
+            ))));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for ChaosInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.interface.set_network(network);
+    }
+
+    async fn status(&self) -> Result<()> {
+        let faults = self.faults("status");
+        self.inject("status", &faults).await?;
+        self.interface.status().await
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        let faults = self.faults("get_balance");
+        self.inject("get_balance", &faults).await?;
+        if faults.contains(&ChaosFault::Stale) {
+            if let Some(balance) = self.last_balance.lock().unwrap().get(address) {
+                log::info!("Chaos - returned stale balance for {}", address);
+                return Ok(*balance);
+            }
+        }
+        let balance = self.interface.get_balance(address).await?;
+        self.last_balance
+            .lock()
+            .unwrap()
+            .insert(address.to_string(), balance);
+        Ok(balance)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        let faults = self.faults("get_utxo");
+        self.inject("get_utxo", &faults).await?;
+        if faults.contains(&ChaosFault::Stale) {
+            if let Some(utxo) = self.last_utxo.lock().unwrap().get(address) {
+                log::info!("Chaos - returned stale utxo for {}", address);
+                return Ok(utxo.clone());
+            }
+        }
+        let utxo = self.interface.get_utxo(address).await?;
+        self.last_utxo
+            .lock()
+            .unwrap()
+            .insert(address.to_string(), utxo.clone());
+        Ok(utxo)
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        let faults = self.faults("broadcast_tx");
+        self.inject("broadcast_tx", &faults).await?;
+        if faults.contains(&ChaosFault::Drop) {
+            // Report success without passing the tx on
+            let tx_hash = tx.hash().encode();
+            log::info!("Chaos - dropped broadcast of {}", tx_hash);
+            return Ok(tx_hash);
+        }
+        self.interface.broadcast_tx(tx).await
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        let faults = self.faults("get_tx");
+        self.inject("get_tx", &faults).await?;
+        self.interface.get_tx(txid).await
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        let faults = self.faults("get_block_headers");
+        self.inject("get_block_headers", &faults).await?;
+        self.interface.get_block_headers().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ChaosScriptConfig, simulated_interface::SimulatedInterface};
+
+    #[tokio::test]
+    async fn test_scripted_faults() {
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let config = ChaosConfig {
+            script: vec![
+                ChaosScriptConfig {
+                    method: Some("status".to_string()),
+                    call: 2,
+                    fault: ChaosFault::Error,
+                },
+                ChaosScriptConfig {
+                    method: Some("broadcast_tx".to_string()),
+                    call: 1,
+                    fault: ChaosFault::Drop,
+                },
+            ],
+            ..Default::default()
+        };
+        let chaos = ChaosInterface::new(Box::new(simulated.clone()), &config);
+
+        assert!(chaos.status().await.is_ok());
+        assert!(chaos.status().await.is_err());
+        assert!(chaos.status().await.is_ok());
+
+        // Dropped broadcast reports success but does not reach the chain
+        let tx = Tx::default();
+        assert_eq!(chaos.broadcast_tx(&tx).await.unwrap(), tx.hash().encode());
+        assert_eq!(simulated.mempool_size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_error_probability() {
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let config = ChaosConfig {
+            error_probability: 1.0,
+            seed: Some(1),
+            ..Default::default()
+        };
+        let chaos = ChaosInterface::new(Box::new(simulated), &config);
+        for _ in 0..5 {
+            assert!(chaos.get_block_headers().await.is_err());
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result},
+};
+
+use crate::{config::CircuitBreakerConfig, failover_interface::is_rejection};
+
+/// Circuit breaker state
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum CircuitState {
+    /// Closed - calls are made, counting consecutive failures
+    Closed,
+    /// Open - calls fail fast until the reset period has passed
+    Open,
+    /// HalfOpen - a trial call is made, if it succeeds the breaker is closed
+    HalfOpen,
+}
+
+/// Circuit breaker, shared so that its state can be reported
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    failure_threshold: u32,
+    reset_period: Duration,
+}
+
+pub type SharedCircuitBreaker = Arc<Mutex<CircuitBreaker>>;
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            failure_threshold: config.failure_threshold.max(1),
+            reset_period: Duration::from_secs(config.reset_period),
+        }
+    }
+
+    pub fn get_state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Return true if a call can be made
+    fn allow_call(&mut self) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            // Only one trial call is allowed per reset period while half open,
+            // a further trial is allowed in case the previous trial call was abandoned
+            CircuitState::Open | CircuitState::HalfOpen => {
+                let reset = self
+                    .opened_at
+                    .is_some_and(|opened_at| opened_at.elapsed() >= self.reset_period);
+                if reset {
+                    self.state = CircuitState::HalfOpen;
+                    self.opened_at = Some(Instant::now());
+                }
+                reset
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        if self.state != CircuitState::Closed {
+            log::info!("circuit breaker closed");
+        }
+        self.state = CircuitState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    fn record_failure(&mut self) {
+        self.consecutive_failures += 1;
+        if self.state == CircuitState::HalfOpen
+            || self.consecutive_failures >= self.failure_threshold
+        {
+            if self.state != CircuitState::Open {
+                log::warn!(
+                    "circuit breaker opened after {} consecutive failures",
+                    self.consecutive_failures
+                );
+            }
+            self.state = CircuitState::Open;
+            self.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Blockchain interface wrapper that applies timeouts to each call
+/// and fails fast, using a circuit breaker, after repeated failures
+pub struct CircuitBreakerInterface {
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+    breaker: SharedCircuitBreaker,
+    query_timeout: Duration,
+    broadcast_timeout: Duration,
+}
+
+impl CircuitBreakerInterface {
+    pub fn new(
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+        config: &CircuitBreakerConfig,
+        breaker: SharedCircuitBreaker,
+    ) -> Self {
+        CircuitBreakerInterface {
+            interface,
+            breaker,
+            query_timeout: Duration::from_secs(config.query_timeout),
+            broadcast_timeout: Duration::from_secs(config.broadcast_timeout),
+        }
+    }
+
+    /// Make the call, subject to the circuit breaker and timeout
+    /// Only transport errors and timeouts count as failures, a rejected request means the upstream is available
+    async fn call<T>(
+        &self,
+        name: &str,
+        timeout: Duration,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        if !self.breaker.lock().unwrap().allow_call() {
+            return Err(Error::IOError(std::io::Error::other(format!(
+                "{} - circuit breaker open",
+                name
+            ))));
+        }
+        let result = match tokio::time::timeout(timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("{} - timed out after {:?}", name, timeout),
+            ))),
+        };
+        let mut breaker = self.breaker.lock().unwrap();
+        match &result {
+            Ok(_) => breaker.record_success(),
+            Err(e) if is_rejection(e) => breaker.record_success(),
+            Err(_) => breaker.record_failure(),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for CircuitBreakerInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.interface.set_network(network);
+    }
+
+    async fn status(&self) -> Result<()> {
+        self.call("status", self.query_timeout, self.interface.status())
+            .await
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        self.call(
+            "get_balance",
+            self.query_timeout,
+            self.interface.get_balance(address),
+        )
+        .await
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        self.call(
+            "get_utxo",
+            self.query_timeout,
+            self.interface.get_utxo(address),
+        )
+        .await
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        self.call(
+            "broadcast_tx",
+            self.broadcast_timeout,
+            self.interface.broadcast_tx(tx),
+        )
+        .await
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        self.call("get_tx", self.query_timeout, self.interface.get_tx(txid))
+            .await
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        self.call(
+            "get_block_headers",
+            self.query_timeout,
+            self.interface.get_block_headers(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulated_interface::SimulatedInterface;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_period: 1,
+            ..Default::default()
+        };
+        let mut breaker = CircuitBreaker::new(&config);
+        assert!(breaker.allow_call());
+        breaker.record_failure();
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+
+        assert!(!breaker.allow_call());
+
+        // Reset period has passed, so one trial call is allowed
+        std::thread::sleep(Duration::from_secs(1));
+        assert!(breaker.allow_call());
+        assert_eq!(breaker.get_state(), CircuitState::HalfOpen);
+        assert!(!breaker.allow_call());
+        breaker.record_failure();
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_secs(1));
+        assert!(breaker.allow_call());
+        breaker.record_success();
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_rejections_leave_breaker_closed() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 2,
+            ..Default::default()
+        };
+        let breaker = Arc::new(Mutex::new(CircuitBreaker::new(&config)));
+        let interface = CircuitBreakerInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &config,
+            breaker.clone(),
+        );
+        // The invalid tx is rejected each time, which does not count as a failure
+        for _ in 0..5 {
+            let result = interface.broadcast_tx(&Tx::default()).await;
+            assert!(matches!(result, Err(Error::BadData(_))));
+        }
+        assert_eq!(breaker.lock().unwrap().get_state(), CircuitState::Closed);
+        assert!(interface.status().await.is_ok());
+    }
+}
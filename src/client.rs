@@ -1,9 +1,12 @@
 // use bitcoin::{secp256k1::Secp256k1, util::key::PrivateKey, Address, PublicKey};
 // use k256::ecdsa::{SigningKey, VerifyingKey};
+use std::fmt;
 
 use chain_gang::{
+    address::{addr_encode, AddressType},
     interface::{Balance, BlockchainInterface, Utxo, UtxoEntry},
     messages::{OutPoint, Tx, TxIn, TxOut},
+    network::Network,
     script::Script,
     transaction::{
         //generate_signature,
@@ -11,12 +14,13 @@ use chain_gang::{
         //sighash::{sighash, SigHashCache, SIGHASH_ALL, SIGHASH_FORKID},
         sighash::{SIGHASH_ALL, SIGHASH_FORKID},
     },
-    util::Hash256,
+    util::{hash160, Hash256},
     wallet::{create_sighash, Wallet},
 };
 
-use crate::config::ClientConfig;
+use crate::{config::ClientConfig, secret::SecretString, util::check_address_network};
 
+#[derive(Clone)]
 pub struct FundRequest {
     pub client_id: String,
     pub satoshi: u64,
@@ -25,13 +29,39 @@ pub struct FundRequest {
     pub locking_script: Vec<u8>,
 }
 
+/// Quoted funding tx - the unsigned tx and the unspents it spends
+pub struct TxQuote {
+    pub tx: Tx,
+    pub inputs: Vec<UtxoEntry>,
+}
+
+impl TxQuote {
+    /// Return the fee, the value of the inputs less the value of the outputs
+    pub fn fee(&self) -> i64 {
+        let inputs: i64 = self.inputs.iter().map(|x| x.value).sum();
+        let outputs: i64 = self.tx.outputs.iter().map(|x| x.satoshis).sum();
+        inputs - outputs
+    }
+}
+
+/// The client's funding key
+/// The key is held as a SecretString, so that it is zeroised when the client is dropped,
+/// the wallet is only built from it while signing
+#[derive(Clone)]
+struct FundingKey {
+    wif_key: SecretString,
+    /// Locking script of the key's address, used for the change
+    locking_script: Script,
+}
+
 /// Represents a Client of the service
-#[derive(Debug, Clone)]
+/// Note that Debug is implemented manually so that the key is not output
+#[derive(Clone)]
 pub struct Client {
     /// Used to identify the client
     pub client_id: String,
-    /// Funding Wallet
-    wallet: Wallet,
+    /// Funding key - None if the client is watch-only
+    key: Option<FundingKey>,
     address: String,
     /// Current funding balance
     balance: Balance,
@@ -39,30 +69,95 @@ pub struct Client {
     unspent: Utxo,
 }
 
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("client_id", &self.client_id)
+            .field("key", &self.key.as_ref().map(|_| "[REDACTED]"))
+            .field("address", &self.address)
+            .field("balance", &self.balance)
+            .field("unspent", &self.unspent)
+            .finish()
+    }
+}
+
 impl Client {
-    /// Create a new
-    pub fn new(config: &ClientConfig) -> Self {
-        let wallet = Wallet::from_wif(&config.wif_key).unwrap_or_else(|_| {
-            panic!(
-                r#"wif_key = "{}" is not a valid WIF key (client_id = "{}")."#,
-                config.wif_key, config.client_id
-            )
-        });
-        let address = wallet.get_address().unwrap_or_else(|_| {
-            panic!(
-                r#"wif_key = "{}" is not a valid WIF key - issues with address (client_id = "{}")."#,
-                config.wif_key, config.client_id
-            )
-        });
-        Client {
+    /// Create a new client, if no wif_key is provided the client is watch-only
+    /// Returns an error if the client's key, address or public_key is not valid
+    pub fn new(config: &ClientConfig, network: Network) -> Result<Self, String> {
+        let (key, address) = match &config.wif_key {
+            Some(wif_key) => {
+                // Note that the wif_key is not included in error messages
+                let wallet = Wallet::from_wif(wif_key.expose_secret()).map_err(|_| {
+                    format!(
+                        r#"wif_key is not a valid WIF key (client_id = "{}")."#,
+                        config.client_id
+                    )
+                })?;
+                let address = wallet.get_address().map_err(|_| {
+                    format!(
+                        r#"wif_key is not a valid WIF key - issues with address (client_id = "{}")."#,
+                        config.client_id
+                    )
+                })?;
+                // The WIF's network is reflected in the address it produces
+                check_address_network(&address, network).map_err(|_| {
+                    format!(
+                        r#"wif_key is not a {:?} WIF key (client_id = "{}")."#,
+                        network, config.client_id
+                    )
+                })?;
+                let key = FundingKey {
+                    wif_key: wif_key.clone(),
+                    locking_script: wallet.get_locking_script(),
+                };
+                (Some(key), address)
+            }
+            None => (None, Client::watch_only_address(config, network)?),
+        };
+        Ok(Client {
             client_id: config.client_id.clone(),
-            wallet,
+            key,
             address,
             balance: Balance::default(),
             unspent: Vec::new(),
+        })
+    }
+
+    /// Return the address of a watch-only client from either its address or public_key
+    fn watch_only_address(config: &ClientConfig, network: Network) -> Result<String, String> {
+        if let Some(address) = &config.address {
+            check_address_network(address, network)
+                .map_err(|e| format!(r#"{} (client_id = "{}")"#, e, config.client_id))?;
+            Ok(address.clone())
+        } else if let Some(public_key) = &config.public_key {
+            let key_as_bytes = hex::decode(public_key)
+                .ok()
+                .filter(|k| k.len() == 33 || k.len() == 65)
+                .ok_or_else(|| {
+                    format!(
+                        r#"public_key = "{}" is not a valid public key (client_id = "{}")."#,
+                        public_key, config.client_id
+                    )
+                })?;
+            Ok(addr_encode(
+                &hash160(&key_as_bytes),
+                AddressType::P2PKH,
+                network,
+            ))
+        } else {
+            Err(format!(
+                r#"Client requires one of wif_key, address or public_key (client_id = "{}")."#,
+                config.client_id
+            ))
         }
     }
 
+    /// Return true if the client has no key and so can not fund transactions
+    pub fn is_watch_only(&self) -> bool {
+        self.key.is_none()
+    }
+
     /// Given an interface query it for the latest balance
     pub async fn update_balance(
         &mut self,
@@ -88,19 +183,19 @@ impl Client {
         self.address.to_string()
     }
 
+    /// Return the block height of the tx, if the client has a confirmed output from it
+    pub fn get_confirmed_height(&self, tx_hash: &str) -> Option<u32> {
+        self.unspent
+            .iter()
+            .find(|x| x.tx_hash == tx_hash && x.height > 0)
+            .map(|x| x.height)
+    }
+
     /// Return the value of the largest unspent UTXO
     fn get_largest_unspent(&self) -> Option<i64> {
         self.unspent.iter().max_by_key(|x| x.value).map(|x| x.value)
     }
 
-    /// Return the smallest unspent that is greater than given satoshi
-    fn get_smallest_unspent(&self, satoshi: u64) -> Option<&UtxoEntry> {
-        // Note unspent is already sorted by value
-        self.unspent
-            .iter()
-            .find(|x| x.value > satoshi.try_into().unwrap())
-    }
-
     /// Given the tx inputs, determine if there is a suitable Utxo for a funding tx
     pub fn has_sufficent_balance(&self, fund_request: &FundRequest) -> Option<bool> {
         let largest_unspent = self.get_largest_unspent()?;
@@ -122,81 +217,66 @@ impl Client {
 
     /// Create one funding transaction
     pub fn create_funding_tx(&mut self, fund_request: &FundRequest) -> Option<Tx> {
-        // Calculate fee...
-        let locking_script_len: u64 = fund_request.locking_script.len() as u64;
-        let fee_estimate: u64 =
-            (((locking_script_len * fund_request.no_of_outpoints as u64) / 1000) * 500) + 750;
-        let total_cost: u64 =
-            (fund_request.satoshi * fund_request.no_of_outpoints as u64) + fee_estimate;
-        // Create a locking script for change
-        let change_script = self.wallet.get_locking_script();
-        // Find smallest funding unspent that is big enough for tx
-        let unspent = self.get_smallest_unspent(total_cost)?;
-        // Create vin
-        let vins: Vec<TxIn> = vec![TxIn {
-            prev_output: OutPoint {
-                hash: Hash256::decode(&unspent.tx_hash).unwrap(),
-                index: unspent.tx_pos,
-            },
-            unlock_script: Script::new(),
-            sequence: 0xffffffff,
-        }];
-        // Create the vout
-        // create vout for change
-        let change = unspent.value - total_cost as i64;
-        assert!(change > 0);
-        let mut vouts: Vec<TxOut> = vec![TxOut {
-            satoshis: change,
-            lock_script: change_script.clone(),
-        }];
+        self.create_combined_funding_tx(std::slice::from_ref(fund_request))
+    }
 
-        // Append the provided script
-        let mut script_pubkey: Script = Script::new();
-        script_pubkey.append_slice(&fund_request.locking_script);
+    /// Create one funding transaction with the outpoints of all the requests
+    /// The change is output 0, followed by each request's outpoints in order
+    pub fn create_combined_funding_tx(&mut self, fund_requests: &[FundRequest]) -> Option<Tx> {
+        let (outputs, fee_estimate) = funding_outputs(fund_requests);
+        self.create_tx(outputs, fee_estimate)
+    }
 
-        let txout = TxOut {
-            satoshis: fund_request.satoshi as i64,
-            lock_script: script_pubkey,
-        };
-        for _ in 0..fund_request.no_of_outpoints {
-            vouts.push(txout.clone());
-        }
+    /// Create and sign a transaction with the outputs, funded from one unspent
+    fn create_tx(&mut self, outputs: Vec<TxOut>, fee_estimate: u64) -> Option<Tx> {
+        // Watch-only clients are unable to sign
+        let key = self.key.as_ref()?;
+        let change_script = &key.locking_script;
+        let (mut tx, unspent) = build_tx(&self.unspent, change_script, outputs, fee_estimate)?;
 
-        let mut tx = Tx {
-            version: 1,
-            inputs: vins,
-            outputs: vouts,
-            lock_time: 0,
-        };
-        // Sign transaction
+        // Sign transaction, the wallet is dropped once the tx is signed
+        let wallet = Wallet::from_wif(key.wif_key.expose_secret()).ok()?;
         let sighash_flags = SIGHASH_ALL | SIGHASH_FORKID;
 
-        let sighash = create_sighash(&tx, 0, &change_script, unspent.value, sighash_flags).unwrap();
-        let signature = self.wallet.sign_sighash(sighash, sighash_flags).unwrap();
+        let sighash = create_sighash(&tx, 0, change_script, unspent.value, sighash_flags).unwrap();
+        let signature = wallet.sign_sighash(sighash, sighash_flags).unwrap();
 
         // insert the ScriptSig (unlock_script)
-        tx.inputs[0].unlock_script = self.wallet.create_unlock_script(&signature);
-
-        // find unspent index
-        let index = self.unspent.iter().position(|x| x == unspent).unwrap();
-
-        // Remove input from unspent
-        self.unspent.remove(index);
-        // Add output to unspent
-        let entry = UtxoEntry {
-            height: 0,
-            tx_pos: 0,
-            tx_hash: tx.hash().encode(),
-            value: change,
-        };
-        self.unspent.push(entry);
-        // Sort unspent by value
-        self.unspent.sort_by_key(|x| x.value);
+        tx.inputs[0].unlock_script = wallet.create_unlock_script(&signature);
+
+        spend_unspent(&mut self.unspent, &unspent, &tx);
 
         // Return the transaction
         Some(tx)
     }
 
+    /// Quote the funding txs for the request without changing the client's unspent.
+    /// The txs are built, but not signed, on a copy of the unspent so the coin selection matches funding.
+    /// Returns None if the client is unable to fund the request
+    pub fn quote_funding_txs(&self, fund_request: &FundRequest) -> Option<Vec<TxQuote>> {
+        // Watch-only clients are unable to fund
+        let change_script = &self.key.as_ref()?.locking_script;
+        let no_of_txs = if fund_request.no_of_outpoints > 1 && fund_request.multiple_tx {
+            fund_request.no_of_outpoints
+        } else {
+            1
+        };
+        let (outputs, fee_estimate) = funding_outputs(std::slice::from_ref(fund_request));
+
+        // Unspents, including the change of the earlier quoted txs
+        let mut unspent = self.unspent.clone();
+        let mut quotes: Vec<TxQuote> = Vec::new();
+        for _ in 0..no_of_txs {
+            let (tx, input) = build_tx(&unspent, change_script, outputs.clone(), fee_estimate)?;
+            spend_unspent(&mut unspent, &input, &tx);
+            quotes.push(TxQuote {
+                tx,
+                inputs: vec![input],
+            });
+        }
+        Some(quotes)
+    }
+
     /// Create no_of_outpoints funding txs each with one outpoint
     pub fn create_multiple_funding_txs(&mut self, fund_request: &FundRequest) -> Vec<Tx> {
         let mut txs: Vec<Tx> = Vec::new();
@@ -214,6 +294,92 @@ impl Client {
     }
 }
 
+/// Return the outputs for the requests, in order, and the fee estimate
+fn funding_outputs(fund_requests: &[FundRequest]) -> (Vec<TxOut>, u64) {
+    // Calculate fee...
+    let locking_script_len: u64 = fund_requests
+        .iter()
+        .map(|r| r.locking_script.len() as u64 * r.no_of_outpoints as u64)
+        .sum();
+    let fee_estimate: u64 = ((locking_script_len / 1000) * 500) + 750;
+    let mut outputs: Vec<TxOut> = Vec::new();
+    for fund_request in fund_requests {
+        // Append the provided script
+        let mut script_pubkey: Script = Script::new();
+        script_pubkey.append_slice(&fund_request.locking_script);
+
+        let txout = TxOut {
+            satoshis: fund_request.satoshi as i64,
+            lock_script: script_pubkey,
+        };
+        for _ in 0..fund_request.no_of_outpoints {
+            outputs.push(txout.clone());
+        }
+    }
+    (outputs, fee_estimate)
+}
+
+/// Build an unsigned transaction with the outputs, funded from the smallest unspent that is big enough.
+/// The change is output 0. Returns the tx and the unspent it spends
+fn build_tx(
+    unspent: &[UtxoEntry],
+    change_script: &Script,
+    outputs: Vec<TxOut>,
+    fee_estimate: u64,
+) -> Option<(Tx, UtxoEntry)> {
+    let total_cost: u64 = outputs.iter().map(|x| x.satoshis as u64).sum::<u64>() + fee_estimate;
+    // Find smallest funding unspent that is big enough for tx
+    // Note unspent is already sorted by value
+    let unspent = unspent
+        .iter()
+        .find(|x| x.value > total_cost.try_into().unwrap())?;
+    // Create vin
+    let vins: Vec<TxIn> = vec![TxIn {
+        prev_output: OutPoint {
+            hash: Hash256::decode(&unspent.tx_hash).unwrap(),
+            index: unspent.tx_pos,
+        },
+        unlock_script: Script::new(),
+        sequence: 0xffffffff,
+    }];
+    // Create the vout
+    // create vout for change
+    let change = unspent.value - total_cost as i64;
+    assert!(change > 0);
+    let mut vouts: Vec<TxOut> = vec![TxOut {
+        satoshis: change,
+        lock_script: change_script.clone(),
+    }];
+    vouts.extend(outputs);
+
+    let tx = Tx {
+        version: 1,
+        inputs: vins,
+        outputs: vouts,
+        lock_time: 0,
+    };
+    Some((tx, unspent.clone()))
+}
+
+/// Remove the tx's input from the unspent, and add its change output
+fn spend_unspent(unspent: &mut Utxo, input: &UtxoEntry, tx: &Tx) {
+    // find unspent index
+    let index = unspent.iter().position(|x| x == input).unwrap();
+
+    // Remove input from unspent
+    unspent.remove(index);
+    // Add output to unspent
+    let entry = UtxoEntry {
+        height: 0,
+        tx_pos: 0,
+        tx_hash: tx.hash().encode(),
+        value: tx.outputs[0].satoshis,
+    };
+    unspent.push(entry);
+    // Sort unspent by value
+    unspent.sort_by_key(|x| x.value);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,12 +475,13 @@ mod tests {
             blockchain_interface: BlockchainInterfaceConfig {
                 interface_type: "test".to_string(),
                 network_type: "testnet".to_string(),
-                url: None,
+                ..Default::default()
             },
 
             client: vec![ClientConfig {
                 client_id: "id1".to_string(),
-                wif_key: "cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".to_string(),
+                wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+                ..Default::default()
             }]
             .into(),
             ..Default::default()
@@ -323,8 +490,9 @@ mod tests {
         // Set up test blockchain
         let blockchain_interface = setup_blockchain(&config).await;
 
+        let network = config.get_network().unwrap();
         let client_config = config.client.unwrap();
-        let mut client = Client::new(&client_config[0]);
+        let mut client = Client::new(&client_config[0], network).unwrap();
 
         let result = client.update_balance(&*blockchain_interface).await;
         assert!(&result.is_ok());
@@ -337,7 +505,7 @@ mod tests {
             satoshi: 123,
             no_of_outpoints: 1,
             multiple_tx: false,
-            locking_script: locking_script,
+            locking_script,
         };
         let tx = client.create_funding_tx(&fund_request).unwrap();
 
@@ -346,12 +514,123 @@ mod tests {
     }
 
     #[tokio::test]
-    #[should_panic]
+    async fn test_quote_funding_txs() {
+        let config = Config {
+            blockchain_interface: BlockchainInterfaceConfig {
+                interface_type: "test".to_string(),
+                network_type: "testnet".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let blockchain_interface = setup_blockchain(&config).await;
+        let client_config = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        let mut client = Client::new(&client_config, config.get_network().unwrap()).unwrap();
+        client.update_balance(&*blockchain_interface).await.unwrap();
+
+        let mut fund_request = FundRequest {
+            client_id: "id1".to_string(),
+            satoshi: 123,
+            no_of_outpoints: 2,
+            multiple_tx: true,
+            locking_script: hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac")
+                .unwrap(),
+        };
+        // Each quoted tx is unsigned, and spends the change of the previous quoted tx
+        let unspent = client.unspent.clone();
+        let quotes = client.quote_funding_txs(&fund_request).unwrap();
+        assert_eq!(client.unspent, unspent);
+        assert_eq!(quotes.len(), 2);
+        for quote in &quotes {
+            assert_eq!(quote.fee(), 750);
+            assert!(quote.tx.inputs[0].unlock_script.0.is_empty());
+        }
+        assert_eq!(quotes[1].inputs[0].tx_hash, quotes[0].tx.hash().encode());
+
+        // Quoting does not change the unspent, so the funding tx spends the quoted input
+        fund_request.multiple_tx = false;
+        let quotes = client.quote_funding_txs(&fund_request).unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].tx.outputs.len(), 3);
+        let tx = client.create_funding_tx(&fund_request).unwrap();
+        assert_eq!(quotes[0].tx.inputs[0].prev_output, tx.inputs[0].prev_output);
+        assert_eq!(quotes[0].tx.outputs, tx.outputs);
+    }
+
+    #[tokio::test]
     async fn test_invalid_wif_key() {
         let client_config: ClientConfig = ClientConfig {
             client_id: "id1".to_string(),
-            wif_key: "EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".to_string(),
+            wif_key: Some("EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        let result = Client::new(&client_config, Network::BSV_Testnet);
+        assert!(result.is_err());
+        // Check the key is not leaked in the error or the config's debug output
+        assert!(!result
+            .unwrap_err()
+            .contains("EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG"));
+        assert!(
+            !format!("{:?}", client_config).contains("EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wif_key_network_mismatch() {
+        // Testnet WIF key used on mainnet
+        let client_config: ClientConfig = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        assert!(Client::new(&client_config, Network::BSV_Testnet).is_ok());
+        assert!(Client::new(&client_config, Network::BSV_Mainnet).is_err());
+
+        // Testnet address used on mainnet
+        let client_config: ClientConfig = ClientConfig {
+            client_id: "id1".to_string(),
+            address: Some("mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF".to_string()),
+            ..Default::default()
+        };
+        assert!(Client::new(&client_config, Network::BSV_Mainnet).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_only_client() {
+        let config = Config {
+            blockchain_interface: BlockchainInterfaceConfig {
+                interface_type: "test".to_string(),
+                network_type: "testnet".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let blockchain_interface = setup_blockchain(&config).await;
+
+        let client_config: ClientConfig = ClientConfig {
+            client_id: "id1".to_string(),
+            address: Some("mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF".to_string()),
+            ..Default::default()
+        };
+        let mut client = Client::new(&client_config, config.get_network().unwrap()).unwrap();
+        assert!(client.is_watch_only());
+
+        let result = client.update_balance(&*blockchain_interface).await;
+        assert!(&result.is_ok());
+
+        let locking_script =
+            hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac").unwrap();
+        let fund_request = FundRequest {
+            client_id: "id1".to_string(),
+            satoshi: 123,
+            no_of_outpoints: 1,
+            multiple_tx: false,
+            locking_script,
         };
-        Client::new(&client_config);
+        assert!(client.create_funding_tx(&fund_request).is_none());
     }
 }
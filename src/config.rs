@@ -3,6 +3,9 @@ use std::{env, net::Ipv4Addr};
 use chain_gang::network::Network;
 use log::debug;
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::secret::SecretString;
 
 /// Blockchain Interface Configuration
 #[derive(Debug, Default, Deserialize, Clone)]
@@ -10,13 +13,230 @@ pub struct BlockchainInterfaceConfig {
     pub interface_type: String,
     pub network_type: String,
     pub url: Option<String>,
+    /// Ordered list of backends, used by the "failover" interface_type
+    pub backend: Option<Vec<BlockchainBackendConfig>>,
+    /// Period in seconds that a failed backend is not used for
+    #[serde(default = "default_cool_down_period")]
+    pub cool_down_period: u64,
+}
+
+fn default_cool_down_period() -> u64 {
+    60
+}
+
+/// Blockchain Backend Configuration - one of the failover interface's backends
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct BlockchainBackendConfig {
+    pub interface_type: String,
+    pub url: Option<String>,
+    /// Name used to report the backend, if not set the url or interface_type is used
+    pub name: Option<String>,
+}
+
+impl BlockchainBackendConfig {
+    /// Return the name used to report the backend
+    pub fn get_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.url.clone())
+            .unwrap_or_else(|| self.interface_type.clone())
+    }
+}
+
+/// Broadcast Configuration - txs are broadcast to all the broadcasters in parallel
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct BroadcastConfig {
+    /// Number of broadcasters that must accept a tx for it to be treated as broadcast
+    pub quorum: usize,
+    pub broadcaster: Vec<BlockchainBackendConfig>,
+}
+
+/// ARC Configuration - used by the "arc" interface_type
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct ArcConfig {
+    pub api_key: Option<String>,
+    /// If set, ARC sends tx status updates to this url (the service's /arc/callback endpoint)
+    pub callback_url: Option<String>,
+    /// Token provided by ARC with each callback
+    pub callback_token: Option<String>,
+}
+
+/// Node JSON-RPC Configuration - used by the "rpc" interface_type
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct RpcConfig {
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Rescan the blockchain when importing client addresses into the node's wallet
+    #[serde(default)]
+    pub rescan: bool,
+}
+
+/// Simulated Chain Configuration - used by the "simulated" interface_type
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct SimulatedConfig {
+    /// Period in seconds between mined blocks
+    pub block_period: Option<u64>,
+    /// If set each broadcast tx is mined in a block of its own as it is accepted
+    #[serde(default)]
+    pub mine_on_broadcast: bool,
+    /// Initial funding of addresses
+    #[serde(default)]
+    pub funding: Vec<SimulatedFundingConfig>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct SimulatedFundingConfig {
+    pub address: String,
+    pub satoshi: i64,
+}
+
+/// Caching Configuration - rate limits and caches requests to the blockchain interface
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct CachingConfig {
+    /// Maximum sustained request rate, if not set requests are not rate limited
+    pub requests_per_second: Option<f64>,
+    /// Number of requests that can be made in a burst
+    #[serde(default)]
+    pub burst: u32,
+    /// Time in seconds that balances and UTXOs are cached for, 0 disables caching
+    #[serde(default)]
+    pub balance_ttl: u64,
+    /// Time in seconds that block headers are cached for, 0 disables caching
+    #[serde(default)]
+    pub header_ttl: u64,
+}
+
+/// Subscription Configuration - websocket feed of address and block events
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct SubscriptionConfig {
+    pub url: String,
+    /// Period in seconds to wait before reconnecting
+    #[serde(default = "default_reconnect_period")]
+    pub reconnect_period: u64,
+}
+
+fn default_reconnect_period() -> u64 {
+    10
+}
+
+/// Circuit Breaker Configuration - timeouts and circuit breaker for blockchain interface calls
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Timeout in seconds for balance, UTXO, header and status queries
+    pub query_timeout: u64,
+    /// Timeout in seconds for broadcasting a tx
+    pub broadcast_timeout: u64,
+    /// Number of consecutive failures before the circuit breaker opens
+    pub failure_threshold: u32,
+    /// Period in seconds the circuit breaker stays open before a trial call is made
+    pub reset_period: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            query_timeout: 30,
+            broadcast_timeout: 30,
+            failure_threshold: 5,
+            reset_period: 30,
+        }
+    }
+}
+
+/// Fault injected by the chaos interface
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChaosFault {
+    /// Return an error without calling the wrapped interface
+    Error,
+    /// Delay the call by `latency_ms`
+    Latency,
+    /// Return the previous balance or UTXO response for the address
+    Stale,
+    /// Report a broadcast as successful without passing it on
+    Drop,
+}
+
+/// Fault injected at a given call number
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChaosScriptConfig {
+    /// Method the call number refers to, if not set it applies to every method
+    pub method: Option<String>,
+    /// Call number, starting at 1
+    pub call: u64,
+    pub fault: ChaosFault,
+}
+
+/// Chaos Configuration - injects faults into the blockchain interface for testing
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /// Probability of a call returning an error
+    pub error_probability: f64,
+    /// Probability of a call being delayed by `latency_ms`
+    pub latency_probability: f64,
+    pub latency_ms: u64,
+    /// Probability of a balance or UTXO query returning a stale response
+    pub stale_probability: f64,
+    /// Probability of a broadcast being dropped
+    pub drop_probability: f64,
+    /// Random number seed, for reproducible runs
+    pub seed: Option<u64>,
+    /// Faults injected at given call numbers
+    pub script: Vec<ChaosScriptConfig>,
+}
+
+/// Recording Configuration - used by the "record" and "replay" interface_types
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct RecordingConfig {
+    /// File that the requests and responses are recorded to or replayed from
+    pub fixture: String,
+    /// The interface_type that is recorded
+    pub interface_type: Option<String>,
+}
+
+/// Authentication Configuration - if present requests require a bearer token
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct AuthConfig {
+    /// SHA-256 hash (hex) of the admin token, required for client management
+    pub admin_token_hash: String,
+}
+
+/// Maps a client certificate to the client_id it is allowed to access
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct ClientSubjectConfig {
+    /// Common name of the client certificate's subject
+    pub common_name: String,
+    pub client_id: String,
+}
+
+/// TLS Configuration - if present the REST API is served over HTTPS
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain
+    pub cert_file: String,
+    /// PEM encoded private key
+    pub key_file: String,
+    /// PEM encoded CA certificates, if set client certificates are verified (mutual TLS)
+    pub client_ca_file: Option<String>,
+    /// If set connections without a client certificate are rejected
+    #[serde(default)]
+    pub client_certificate_required: bool,
+    #[serde(default)]
+    pub client_subject: Vec<ClientSubjectConfig>,
 }
 
 /// Client Configuration
+/// A client without a `wif_key` is watch-only, it is identified by either its `address` or `public_key`
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct ClientConfig {
     pub client_id: String,
-    pub wif_key: String,
+    pub wif_key: Option<SecretString>,
+    pub address: Option<String>,
+    pub public_key: Option<String>,
+    /// SHA-256 hash (hex) of the client's API key
+    pub api_key_hash: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize, Clone)]
@@ -27,6 +247,9 @@ pub struct LoggingConfig {
 #[derive(Debug, Default, Deserialize, Clone)]
 pub struct ServiceConfig {
     pub utxo_refresh_period: u64,
+    /// Period in seconds between attempts to reconnect to the blockchain, if unreachable on startup
+    #[serde(default = "default_reconnect_period")]
+    pub reconnect_period: u64,
 }
 
 #[derive(Debug, Default, Deserialize, Clone)]
@@ -55,10 +278,27 @@ impl Default for WebInterfaceConfig {
 pub struct Config {
     pub blockchain_interface: BlockchainInterfaceConfig,
     pub web_interface: WebInterfaceConfig,
+    /// If set, client management is served on this address and port rather than the web_interface
+    pub admin_interface: Option<WebInterfaceConfig>,
     pub logging: LoggingConfig,
     pub service: ServiceConfig,
     pub client: Option<Vec<ClientConfig>>,
     pub dynamic_config: DynamicConfigConfig,
+    pub broadcast: Option<BroadcastConfig>,
+    #[serde(default)]
+    pub arc: ArcConfig,
+    #[serde(default)]
+    pub rpc: RpcConfig,
+    #[serde(default)]
+    pub simulated: SimulatedConfig,
+    pub caching: Option<CachingConfig>,
+    pub subscription: Option<SubscriptionConfig>,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub recording: Option<RecordingConfig>,
+    pub chaos: Option<ChaosConfig>,
+    pub auth: Option<AuthConfig>,
+    pub tls: Option<TlsConfig>,
 }
 
 impl Config {
@@ -68,6 +308,8 @@ impl Config {
             "mainnet" => Ok(Network::BSV_Mainnet),
             "testnet" => Ok(Network::BSV_Testnet),
             "stn" => Ok(Network::BSV_STN),
+            // Regtest uses the same address and key prefixes as testnet
+            "regtest" => Ok(Network::BSV_Testnet),
             _ => Err("unable to decode network"),
         }
     }
@@ -90,9 +332,11 @@ impl Config {
 fn read_config(filename: &str) -> Result<Config, String> {
     debug!("read_config = {}", &filename);
     // Given filename read the config
-    let content = std::fs::read_to_string(filename).map_err(|e| e.to_string())?;
-    let config = toml::from_str(&content).map_err(|e| e.to_string())?;
-    Ok(config)
+    let mut content = std::fs::read_to_string(filename).map_err(|e| e.to_string())?;
+    // Only report the error message, as the error's source snippet may contain key material
+    let config = toml::from_str(&content).map_err(|e: toml::de::Error| e.message().to_string());
+    content.zeroize();
+    config
 }
 
 /// Read the config from environment variable, if not read from filename
@@ -1,6 +1,7 @@
 use crate::config::{ClientConfig, Config};
 
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 // Represents the service's dynamically configurable elements
 
@@ -15,15 +16,18 @@ pub struct DynamicConfig {
 }
 
 fn read_dynamic_config(filename: &str) -> Result<FileContents, String> {
-    let content = std::fs::read_to_string(filename).map_err(|e| e.to_string())?;
-    let config = toml::from_str(&content).map_err(|e| e.to_string())?;
-    Ok(config)
+    let mut content = std::fs::read_to_string(filename).map_err(|e| e.to_string())?;
+    // Only report the error message, as the error's source snippet may contain key material
+    let config = toml::from_str(&content).map_err(|e: toml::de::Error| e.message().to_string());
+    content.zeroize();
+    config
 }
 
 fn save_dynamic_config(filename: &str, file_contents: &FileContents) -> std::io::Result<()> {
-    let content = toml::to_string(file_contents).unwrap();
-    std::fs::write(filename, content)?;
-    Ok(())
+    let mut content = toml::to_string(file_contents).unwrap();
+    let result = std::fs::write(filename, &content);
+    content.zeroize();
+    result
 }
 
 impl DynamicConfig {
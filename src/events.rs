@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api_error::ApiError,
+    rest_api::{authorize, AppState},
+    service::BlockchainConnectionStatus,
+};
+
+/// Number of events buffered for each subscriber, slower subscribers miss events
+const EVENT_BUFFER: usize = 256;
+
+/// Period between keep-alive comments, so that idle streams are not closed by proxies
+const KEEP_ALIVE_PERIOD: Duration = Duration::from_secs(15);
+
+/// Event published by the service
+#[derive(Debug, Serialize, Clone, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServiceEvent {
+    /// Funding transactions were created and broadcast
+    FundingCreated {
+        client_id: String,
+        txids: Vec<String>,
+        satoshi: u64,
+        no_of_outpoints: u32,
+    },
+    /// A funding transaction was not accepted by the blockchain
+    BroadcastFailed {
+        client_id: Option<String>,
+        txid: String,
+        reason: String,
+    },
+    /// A funding transaction was mined.
+    /// The block_height is not known if it was confirmed by a later funding transaction that spends its change.
+    TxConfirmed {
+        client_id: Option<String>,
+        txid: String,
+        block_height: Option<u64>,
+    },
+    BalanceUpdated {
+        client_id: String,
+        confirmed: u64,
+        unconfirmed: u64,
+    },
+    ClientAdded {
+        client_id: String,
+    },
+    ClientRemoved {
+        client_id: String,
+    },
+    /// The service's connection to the blockchain changed, sent to all subscribers
+    BlockchainStatusChanged {
+        blockchain_status: BlockchainConnectionStatus,
+    },
+}
+
+impl ServiceEvent {
+    /// Return the SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServiceEvent::FundingCreated { .. } => "funding_created",
+            ServiceEvent::BroadcastFailed { .. } => "broadcast_failed",
+            ServiceEvent::TxConfirmed { .. } => "tx_confirmed",
+            ServiceEvent::BalanceUpdated { .. } => "balance_updated",
+            ServiceEvent::ClientAdded { .. } => "client_added",
+            ServiceEvent::ClientRemoved { .. } => "client_removed",
+            ServiceEvent::BlockchainStatusChanged { .. } => "blockchain_status_changed",
+        }
+    }
+
+    /// Return true if the event should be sent to a subscriber filtered by the client_id
+    pub fn matches(&self, filter: Option<&str>) -> bool {
+        let client_id = match self {
+            ServiceEvent::FundingCreated { client_id, .. }
+            | ServiceEvent::BalanceUpdated { client_id, .. }
+            | ServiceEvent::ClientAdded { client_id }
+            | ServiceEvent::ClientRemoved { client_id } => Some(client_id),
+            ServiceEvent::BroadcastFailed { client_id, .. }
+            | ServiceEvent::TxConfirmed { client_id, .. } => client_id.as_ref(),
+            ServiceEvent::BlockchainStatusChanged { .. } => return true,
+        };
+        match filter {
+            Some(filter) => client_id.is_some_and(|id| id == filter),
+            None => true,
+        }
+    }
+
+    /// Return the event in SSE wire format
+    fn to_sse(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        format!("event: {}\ndata: {}\n\n", self.name(), data)
+    }
+}
+
+/// Publishes service events to the subscribers
+pub struct EventBus {
+    sender: broadcast::Sender<ServiceEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        EventBus { sender }
+    }
+
+    /// Send the event to the subscribers, if any
+    pub fn publish(&self, event: ServiceEvent) {
+        log::debug!("publish {:?}", &event);
+        // An error only indicates that there are no subscribers
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServiceEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct EventsQuery {
+    /// Only send events for this client
+    client_id: Option<String>,
+}
+
+/// Stream of service events (server-sent events)
+/// Subscribing to all clients requires the admin token, a client can subscribe to its own events
+#[utoipa::path(
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Stream of events, as `text/event-stream`", body = ServiceEvent, content_type = "text/event-stream"),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[get("/events")]
+pub async fn events(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let filter = query.into_inner().client_id;
+    let receiver = {
+        let service = data.service.lock().await;
+        authorize(&data, &service, &req, filter.as_deref())?;
+        if let Some(client_id) = &filter {
+            if !service.is_client_id_valid(client_id) {
+                return Err(ApiError::unknown_client(client_id));
+            }
+        }
+        service.subscribe_events()
+    };
+
+    let stream = futures::stream::unfold(receiver, move |mut receiver| {
+        let filter = filter.clone();
+        async move {
+            loop {
+                let message = tokio::select! {
+                    event = receiver.recv() => match event {
+                        Ok(event) if event.matches(filter.as_deref()) => event.to_sse(),
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(missed)) => {
+                            log::warn!("events - subscriber missed {} events", missed);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => return None,
+                    },
+                    _ = tokio::time::sleep(KEEP_ALIVE_PERIOD) => ": keep-alive\n\n".to_string(),
+                };
+                return Some((
+                    Ok::<_, actix_web::Error>(web::Bytes::from(message)),
+                    receiver,
+                ));
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_filter() {
+        let bus = EventBus::new();
+        let mut receiver = bus.subscribe();
+        bus.publish(ServiceEvent::ClientAdded {
+            client_id: "client1".to_string(),
+        });
+        bus.publish(ServiceEvent::BlockchainStatusChanged {
+            blockchain_status: BlockchainConnectionStatus::Connected,
+        });
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.name(), "client_added");
+        assert!(event.matches(Some("client1")));
+        assert!(!event.matches(Some("client2")));
+        assert!(event.matches(None));
+        assert_eq!(
+            event.to_sse(),
+            "event: client_added\ndata: {\"event\":\"client_added\",\"client_id\":\"client1\"}\n\n"
+        );
+
+        // Blockchain status events are sent to all subscribers
+        let event = receiver.recv().await.unwrap();
+        assert!(event.matches(Some("client2")));
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result},
+};
+
+/// Shared record of the backend that last successfully served a request
+pub type ActiveBackend = Arc<Mutex<Option<String>>>;
+
+/// One of the FailoverInterface's blockchain interfaces
+struct Backend {
+    name: String,
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+    /// If set the backend is unhealthy and is not used until this time
+    cool_down_until: Mutex<Option<Instant>>,
+}
+
+/// Blockchain interface that tries each of its backends in order,
+/// backends that fail are placed in a cool-down period before they are tried again
+pub struct FailoverInterface {
+    backends: Vec<Backend>,
+    cool_down_period: Duration,
+    active_backend: ActiveBackend,
+}
+
+/// Return true if the error is the backend rejecting the request, rather than the backend being unavailable.
+/// Interfaces report transport and timeout errors as IOError, and rejections as BadData or BadArgument.
+/// The WhatsOnChain and UaaS interfaces are wrapped by HttpErrorInterface, so that their upstream failures are IOError
+pub fn is_rejection(error: &Error) -> bool {
+    matches!(error, Error::BadData(_) | Error::BadArgument(_))
+}
+
+/// Try each backend in turn, returning the first successful result
+/// If `$is_final` is provided, errors that it matches are returned without failing over
+macro_rules! try_backends {
+    ($self:ident, $call:ident ( $($arg:expr),* )) => {
+        try_backends!($self, $call($($arg),*), |_: &Error| false)
+    };
+    ($self:ident, $call:ident ( $($arg:expr),* ), $is_final:expr) => {{
+        let mut last_error: Option<Error> = None;
+        for index in $self.backend_order() {
+            let backend = &$self.backends[index];
+            match backend.interface.$call($($arg),*).await {
+                Ok(value) => {
+                    $self.mark_healthy(index);
+                    return Ok(value);
+                }
+                Err(e) if $is_final(&e) => {
+                    log::info!("{} - backend {} rejected {:?}", stringify!($call), backend.name, e);
+                    $self.mark_healthy(index);
+                    return Err(e);
+                }
+                Err(e) => {
+                    log::warn!("{} - backend {} failed {:?}", stringify!($call), backend.name, e);
+                    $self.mark_unhealthy(index);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::BadArgument("No backends configured".to_string())))
+    }};
+}
+
+impl FailoverInterface {
+    pub fn new(cool_down_period: Duration, active_backend: ActiveBackend) -> Self {
+        FailoverInterface {
+            backends: Vec::new(),
+            cool_down_period,
+            active_backend,
+        }
+    }
+
+    /// Add a backend, backends are tried in the order they are added
+    pub fn add_backend(
+        &mut self,
+        name: &str,
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+    ) {
+        self.backends.push(Backend {
+            name: name.to_string(),
+            interface,
+            cool_down_until: Mutex::new(None),
+        });
+    }
+
+    /// Return the order to try the backends, healthy backends first followed by those in cool-down
+    fn backend_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (healthy, cooling): (Vec<usize>, Vec<usize>) =
+            (0..self.backends.len()).partition(|index| {
+                match *self.backends[*index].cool_down_until.lock().unwrap() {
+                    Some(until) => until <= now,
+                    None => true,
+                }
+            });
+        healthy.into_iter().chain(cooling).collect()
+    }
+
+    fn mark_healthy(&self, index: usize) {
+        let backend = &self.backends[index];
+        *backend.cool_down_until.lock().unwrap() = None;
+        let mut active_backend = self.active_backend.lock().unwrap();
+        if active_backend.as_deref() != Some(backend.name.as_str()) {
+            log::info!("Active blockchain backend is now {}", backend.name);
+            *active_backend = Some(backend.name.clone());
+        }
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        *self.backends[index].cool_down_until.lock().unwrap() =
+            Some(Instant::now() + self.cool_down_period);
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for FailoverInterface {
+    fn set_network(&mut self, network: &Network) {
+        for backend in &mut self.backends {
+            backend.interface.set_network(network);
+        }
+    }
+
+    async fn status(&self) -> Result<()> {
+        try_backends!(self, status())
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        try_backends!(self, get_balance(address))
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        try_backends!(self, get_utxo(address))
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        // A tx rejected by one backend would be rejected by all, so it is not sent to the others
+        try_backends!(self, broadcast_tx(tx), is_rejection)
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        try_backends!(self, get_tx(txid))
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        try_backends!(self, get_block_headers())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_gang::interface::UaaSInterface;
+
+    use crate::{
+        chaos_interface::ChaosInterface,
+        config::ChaosConfig,
+        http_error_interface::{start_unavailable_server, HttpErrorInterface},
+        simulated_interface::SimulatedInterface,
+    };
+
+    fn is_cooling_down(interface: &FailoverInterface, index: usize) -> bool {
+        interface.backends[index]
+            .cool_down_until
+            .lock()
+            .unwrap()
+            .is_some()
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_failover() {
+        let unavailable = ChaosInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &ChaosConfig {
+                error_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        let mut interface =
+            FailoverInterface::new(Duration::from_secs(60), Arc::new(Mutex::new(None)));
+        interface.add_backend("unavailable", Box::new(unavailable));
+        interface.add_backend(
+            "simulated1",
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+        );
+        interface.add_backend(
+            "simulated2",
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+        );
+
+        // The unavailable backend is failed over, the invalid tx is rejected by the next backend
+        let result = interface.broadcast_tx(&Tx::default()).await;
+        assert!(matches!(result, Err(Error::BadData(_))));
+        assert!(is_cooling_down(&interface, 0));
+        assert!(!is_cooling_down(&interface, 1));
+        assert!(!is_cooling_down(&interface, 2));
+        assert_eq!(
+            interface.active_backend.lock().unwrap().as_deref(),
+            Some("simulated1")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_broadcast_failover_upstream_error() {
+        // The primary responds with 503, which is failed over rather than treated as a rejection
+        let url = start_unavailable_server();
+        let mut uaas = UaaSInterface::new(&url).unwrap();
+        uaas.set_network(&Network::BSV_Testnet);
+        let mut interface =
+            FailoverInterface::new(Duration::from_secs(60), Arc::new(Mutex::new(None)));
+        interface.add_backend(&url, Box::new(HttpErrorInterface::new(Box::new(uaas))));
+        interface.add_backend(
+            "simulated",
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+        );
+
+        let result = interface.broadcast_tx(&Tx::default()).await;
+        // The invalid tx reaches the secondary, which rejects it
+        assert!(matches!(result, Err(Error::BadData(_))));
+        assert!(is_cooling_down(&interface, 0));
+        assert!(!is_cooling_down(&interface, 1));
+        assert_eq!(
+            interface.active_backend.lock().unwrap().as_deref(),
+            Some("simulated")
+        );
+    }
+}
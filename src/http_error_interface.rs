@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result},
+};
+
+/// Return the HTTP status reported in the error message, if any
+/// For example "response.status() = 503 Service Unavailable"
+fn http_status(message: &str) -> Option<u16> {
+    let position = message.to_ascii_lowercase().find("status")?;
+    message[position..]
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|token| token.len() == 3)
+        .and_then(|token| token.parse().ok())
+}
+
+/// Return true if the error message is the upstream rejecting the request, a 4xx status
+/// other than a request timeout or rate limit
+fn is_rejected_status(message: &str) -> bool {
+    match http_status(message) {
+        Some(408 | 429) => false,
+        Some(status) => (400..500).contains(&status),
+        None => false,
+    }
+}
+
+/// Report the upstream and transport failures, that the wrapped interface reports as BadData, as IOError
+fn classify<T>(result: Result<T>) -> Result<T> {
+    match result {
+        Err(Error::BadData(message)) if !is_rejected_status(&message) => {
+            Err(Error::IOError(std::io::Error::other(message)))
+        }
+        result => result,
+    }
+}
+
+/// Blockchain interface wrapper for HTTP interfaces (WhatsOnChain and UaaS) that report all errors as BadData.
+/// Only 4xx responses are reported as BadData (rejections), transport errors and other responses
+/// (for example 5xx and 429) are reported as IOError, so that they cause failover and open the circuit breaker
+pub struct HttpErrorInterface {
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+}
+
+impl HttpErrorInterface {
+    pub fn new(interface: Box<dyn BlockchainInterface + Send + Sync>) -> Self {
+        HttpErrorInterface { interface }
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for HttpErrorInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.interface.set_network(network);
+    }
+
+    async fn status(&self) -> Result<()> {
+        classify(self.interface.status().await)
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        classify(self.interface.get_balance(address).await)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        classify(self.interface.get_utxo(address).await)
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        classify(self.interface.broadcast_tx(tx).await)
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        classify(self.interface.get_tx(txid).await)
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        classify(self.interface.get_block_headers().await)
+    }
+}
+
+/// Start a mock HTTP server that responds to every request with 503, returning its url
+#[cfg(test)]
+pub(crate) fn start_unavailable_server() -> String {
+    use actix_web::{App, HttpResponse, HttpServer};
+
+    let server = HttpServer::new(|| {
+        App::new().default_service(actix_web::web::to(|| async {
+            HttpResponse::ServiceUnavailable().body("Service Unavailable")
+        }))
+    })
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    format!("http://{}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_gang::interface::UaaSInterface;
+
+    #[test]
+    fn test_classify() {
+        let rejected = classify::<()>(Err(Error::BadData(
+            "response.status() = 400 Bad Request".to_string(),
+        )));
+        assert!(matches!(rejected, Err(Error::BadData(_))));
+        for message in [
+            "response.status() = 503 Service Unavailable",
+            "response.status() = 429 Too Many Requests",
+            "error sending request for url (https://api.whatsonchain.com/v1/bsv/test/chain/info)",
+        ] {
+            let result = classify::<()>(Err(Error::BadData(message.to_string())));
+            assert!(matches!(result, Err(Error::IOError(_))));
+        }
+        let result = classify::<()>(Err(Error::BadArgument("invalid".to_string())));
+        assert!(matches!(result, Err(Error::BadArgument(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_unavailable_backend() {
+        let url = start_unavailable_server();
+        let mut uaas = UaaSInterface::new(&url).unwrap();
+        uaas.set_network(&Network::BSV_Testnet);
+        let interface = HttpErrorInterface::new(Box::new(uaas));
+
+        // The 503 responses are reported as transport errors
+        let result = interface
+            .get_balance("mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF")
+            .await;
+        assert!(matches!(result, Err(Error::IOError(_))));
+        let result = interface.broadcast_tx(&Tx::default()).await;
+        assert!(matches!(result, Err(Error::IOError(_))));
+    }
+}
@@ -4,31 +4,48 @@ use tokio::time;
 use actix_web::{web, App, HttpServer};
 use async_mutex::Mutex;
 
+mod api_error;
+mod arc_interface;
+mod auth;
 mod blockchain_factory;
+mod broadcaster;
+mod caching_interface;
+mod chaos_interface;
+mod circuit_breaker;
 mod client;
 mod config;
 mod dynamic_config;
+mod events;
+mod failover_interface;
+mod http_error_interface;
+mod openapi;
+mod recording_interface;
 mod rest_api;
+mod rest_api_v2;
+mod rpc_interface;
+mod secret;
 mod service;
+mod simulated_interface;
+mod subscription;
+mod tls;
 mod util;
 
 use crate::{
-    config::{get_config, Config},
-    rest_api::{
-        add_client, balance, delete_client, get_address, get_funds, index, status, update_clients,
-        AppState,
-    },
+    config::{get_config, WebInterfaceConfig},
+    rest_api::{api_routes, reconnect_blockchain, update_clients, AppState},
     service::Service,
+    subscription::run_subscription,
+    tls::{on_connect, server_config},
 };
 
-// Given the config return the websever ip address and port
-fn get_addr(config: &Config) -> (Ipv4Addr, u16) {
-    let port = config.web_interface.port;
+// Given the web interface config return the websever ip address and port
+fn get_addr(web_interface: &WebInterfaceConfig) -> (Ipv4Addr, u16) {
+    let port = web_interface.port;
     match env::var_os("APP_ENV") {
         // Allow all access in docker
         // (required as otherwise the localmachine can not access the webserver)
         Some(content) if content == "docker" => (Ipv4Addr::new(0, 0, 0, 0), port),
-        Some(_) | None => (config.web_interface.address, port),
+        Some(_) | None => (web_interface.address, port),
     }
 }
 
@@ -44,9 +61,25 @@ async fn main() -> std::io::Result<()> {
     let service = Service::new(&config).await;
     let app_state = web::Data::new(AppState {
         service: Mutex::new(service),
+        arc_callback_token: config.arc.callback_token.clone(),
+        auth: config.auth.clone(),
+        certificate_clients: config
+            .tls
+            .iter()
+            .flat_map(|tls| &tls.client_subject)
+            .map(|s| (s.common_name.clone(), s.client_id.clone()))
+            .collect(),
     });
     let app_state2 = app_state.clone();
-    let addr = get_addr(&config);
+    let addr = get_addr(&config.web_interface);
+    // The admin interface is always bound to its configured address, so that it is not exposed in docker
+    let admin_addr = config
+        .admin_interface
+        .as_ref()
+        .map(|admin_interface| (admin_interface.address, admin_interface.port));
+    let tls_config = config.tls.as_ref().map(|tls| {
+        server_config(tls).unwrap_or_else(|e| panic!("Unable to configure TLS - {}", e))
+    });
 
     // Setup periodic task
     let utxo_refresh_period = config.service.utxo_refresh_period;
@@ -60,24 +93,73 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
-    HttpServer::new(move || {
+    // Reconnect to the blockchain, if unreachable on startup
+    let app_state3 = app_state.clone();
+    let reconnect_period = config.service.reconnect_period;
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(reconnect_period));
+        loop {
+            interval.tick().await;
+            if reconnect_blockchain(app_state3.clone()).await {
+                break;
+            }
+        }
+    });
+
+    // Setup the optional subscription, which refreshes clients as events are received
+    if let Some(subscription) = &config.subscription {
+        let reconnect_period = Duration::from_secs(subscription.reconnect_period);
+        tokio::spawn(run_subscription(
+            subscription.url.clone(),
+            reconnect_period,
+            app_state.clone(),
+        ));
+    }
+
+    // Client management is served on the admin interface, if configured
+    let separate_admin = admin_addr.is_some();
+    let public_state = app_state.clone();
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(app_state.clone())
-            .service(index)
-            .service(status)
-            .service(balance)
-            .service(get_funds)
-            .service(add_client)
-            .service(delete_client)
-            .service(get_address)
+            .app_data(public_state.clone())
+            .configure(api_routes(true, !separate_admin))
     })
-    .bind(addr)
+    .on_connect(on_connect);
+    let server = match &tls_config {
+        Some(tls_config) => server.bind_rustls_0_23(addr, tls_config.clone()),
+        None => server.bind(addr),
+    }
     .unwrap_or_else(|e| {
         panic!(
             r#"Unable to connect to address/port "{:?}". Error = {:?}"#,
             addr, e
         )
     })
-    .run()
-    .await
+    .run();
+
+    match admin_addr {
+        Some(admin_addr) => {
+            let admin_server = HttpServer::new(move || {
+                App::new()
+                    .app_data(app_state.clone())
+                    .configure(api_routes(false, true))
+            })
+            .on_connect(on_connect);
+            let admin_server = match tls_config {
+                Some(tls_config) => admin_server.bind_rustls_0_23(admin_addr, tls_config),
+                None => admin_server.bind(admin_addr),
+            }
+            .unwrap_or_else(|e| {
+                panic!(
+                    r#"Unable to connect to admin address/port "{:?}". Error = {:?}"#,
+                    admin_addr, e
+                )
+            })
+            .run();
+            futures::future::try_join(server, admin_server)
+                .await
+                .map(|_| ())
+        }
+        None => server.await,
+    }
 }
@@ -0,0 +1,164 @@
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+use utoipa::{
+    openapi::{
+        path::PathItem,
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Deprecated,
+    },
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::{events, rest_api, rest_api_v2};
+
+/// OpenAPI document, generated from the REST API handlers and types
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Financing Service REST API"),
+    paths(
+        rest_api::index,
+        rest_api::status,
+        rest_api::get_funds,
+        rest_api::get_funds_batch,
+        rest_api::get_funds_quote,
+        rest_api::add_client,
+        rest_api::delete_client,
+        rest_api::arc_callback,
+        rest_api::get_address,
+        rest_api::balance,
+    ),
+    modifiers(&BearerSecurity, &Version2)
+)]
+pub struct ApiDoc;
+
+/// Version 2 of the API, nested under /v2
+#[derive(OpenApi)]
+#[openapi(paths(
+    rest_api::status,
+    rest_api_v2::get_funds,
+    rest_api_v2::get_funds_batch,
+    rest_api_v2::get_funds_quote,
+    rest_api::add_client,
+    rest_api::delete_client,
+    rest_api::get_address,
+    rest_api::balance,
+    events::events,
+))]
+struct V2Api;
+
+/// Return the operations of the path item
+fn operations(
+    path_item: &mut PathItem,
+) -> impl Iterator<Item = &mut utoipa::openapi::path::Operation> {
+    [
+        &mut path_item.get,
+        &mut path_item.post,
+        &mut path_item.delete,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Adds the version 2 paths, and marks the version 1 paths that they replace as deprecated
+struct Version2;
+
+impl Modify for Version2 {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let mut v2 = V2Api::openapi();
+        for (path, path_item) in v2.paths.paths.iter_mut() {
+            // Operation ids must be unique across the document
+            for operation in operations(path_item) {
+                operation.operation_id =
+                    operation.operation_id.as_ref().map(|id| format!("{id}_v2"));
+            }
+            if let Some(v1_path_item) = openapi.paths.paths.get_mut(path) {
+                for operation in operations(v1_path_item) {
+                    operation.deprecated = Some(Deprecated::True);
+                }
+            }
+        }
+        *openapi = std::mem::take(openapi).nest("/v2", v2);
+    }
+}
+
+/// Adds the bearer token security scheme, used if authentication is configured
+struct BearerSecurity;
+
+impl Modify for BearerSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Get OpenAPI document endpoint
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(ApiDoc::openapi().to_json().unwrap())
+}
+
+/// Interactive API docs endpoint, Swagger UI (bundled into the binary) which renders /openapi.json
+pub fn docs(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::redirect("/docs", "/docs/"))
+        .service(SwaggerUi::new("/docs/{_:.*}").config(Config::new(["/openapi.json"])));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_document() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let fund = &openapi["paths"]["/fund"]["post"];
+        assert!(fund["responses"]["502"].is_object());
+        assert!(openapi["paths"]["/client/{client_id}/balance"]["get"].is_object());
+        let schemas = &openapi["components"]["schemas"];
+        assert!(schemas["FundingRequest"]["properties"]["satoshi"]["type"] == "integer");
+        assert!(schemas["ErrorCode"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&"UNKNOWN_CLIENT".into()));
+        assert!(openapi["components"]["securitySchemes"]["bearer"].is_object());
+
+        // Version 2 paths, which replace the deprecated version 1 paths
+        assert_eq!(fund["deprecated"], true);
+        let fund_v2 = &openapi["paths"]["/v2/fund"]["post"];
+        assert_eq!(fund_v2["operationId"], "get_funds_v2");
+        assert!(fund_v2.get("deprecated").is_none());
+        assert_eq!(
+            openapi["paths"]["/v2/status"]["get"]["operationId"],
+            "status_v2"
+        );
+        assert!(schemas["FundingResponseV2"].is_object());
+    }
+
+    #[actix_web::test]
+    async fn test_docs() {
+        use actix_web::{http::StatusCode, test, App};
+
+        let app = test::init_service(App::new().configure(docs)).await;
+        let req = test::TestRequest::get().uri("/docs").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+
+        // The Swagger UI assets are served by the service, rather than loaded from a CDN
+        let req = test::TestRequest::get().uri("/docs/").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert!(!String::from_utf8_lossy(&body).contains("https://"));
+        let req = test::TestRequest::get()
+            .uri("/docs/swagger-initializer.js")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert!(String::from_utf8_lossy(&body).contains("/openapi.json"));
+        let req = test::TestRequest::get()
+            .uri("/docs/swagger-ui-bundle.js")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
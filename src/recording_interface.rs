@@ -0,0 +1,378 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Cursor, Write},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo, UtxoEntry},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result, Serializable},
+};
+
+use crate::util::tx_as_hexstr;
+
+/// A recorded request and response, stored as one JSON line in the fixture file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Recording {
+    pub call: String,
+    pub arg: String,
+    /// Response value, if the call succeeded
+    pub response: Option<Value>,
+    /// Error message, if the call failed
+    pub error: Option<String>,
+    /// Error kind, if the call failed, so that the same error is returned on replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<RecordedErrorKind>,
+}
+
+/// The kind of a recorded error, errors of other kinds are replayed as BadData
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedErrorKind {
+    IOError,
+    BadData,
+    BadArgument,
+    InvalidOperation,
+}
+
+/// Return the kind and message of the error
+fn error_to_recording(error: &Error) -> (Option<RecordedErrorKind>, String) {
+    match error {
+        Error::IOError(e) => (Some(RecordedErrorKind::IOError), e.to_string()),
+        Error::BadData(e) => (Some(RecordedErrorKind::BadData), e.clone()),
+        Error::BadArgument(e) => (Some(RecordedErrorKind::BadArgument), e.clone()),
+        Error::InvalidOperation(e) => (Some(RecordedErrorKind::InvalidOperation), e.clone()),
+        e => (None, format!("{:?}", e)),
+    }
+}
+
+/// Return the recorded error, as the same kind of error
+fn recording_to_error(error_kind: Option<RecordedErrorKind>, error: String) -> Error {
+    match error_kind {
+        Some(RecordedErrorKind::IOError) => Error::IOError(std::io::Error::other(error)),
+        Some(RecordedErrorKind::BadArgument) => Error::BadArgument(error),
+        Some(RecordedErrorKind::InvalidOperation) => Error::InvalidOperation(error),
+        Some(RecordedErrorKind::BadData) | None => Error::BadData(error),
+    }
+}
+
+fn balance_to_value(balance: &Balance) -> Value {
+    json!({"confirmed": balance.confirmed, "unconfirmed": balance.unconfirmed})
+}
+
+fn value_to_balance(value: &Value) -> Option<Balance> {
+    Some(Balance {
+        confirmed: value["confirmed"].as_u64()?,
+        unconfirmed: value["unconfirmed"].as_u64()?,
+    })
+}
+
+fn utxo_to_value(utxo: &Utxo) -> Value {
+    utxo.iter()
+        .map(|entry| {
+            json!({"height": entry.height, "tx_pos": entry.tx_pos, "tx_hash": entry.tx_hash, "value": entry.value})
+        })
+        .collect()
+}
+
+fn value_to_utxo(value: &Value) -> Option<Utxo> {
+    value
+        .as_array()?
+        .iter()
+        .map(|entry| {
+            Some(UtxoEntry {
+                height: entry["height"].as_u64()? as u32,
+                tx_pos: entry["tx_pos"].as_u64()? as u32,
+                tx_hash: entry["tx_hash"].as_str()?.to_string(),
+                value: entry["value"].as_i64()?,
+            })
+        })
+        .collect()
+}
+
+fn value_to_tx(value: &Value) -> Option<Tx> {
+    let tx_as_bytes = hex::decode(value.as_str()?).ok()?;
+    Tx::read(&mut Cursor::new(tx_as_bytes)).ok()
+}
+
+/// Blockchain interface wrapper that records every request and response to a fixture file
+pub struct RecordingInterface {
+    interface: Box<dyn BlockchainInterface + Send + Sync>,
+    file: Mutex<File>,
+}
+
+impl RecordingInterface {
+    pub fn new(
+        interface: Box<dyn BlockchainInterface + Send + Sync>,
+        fixture: &str,
+    ) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(fixture)?;
+        Ok(RecordingInterface {
+            interface,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record the call and its result, returning the result
+    fn record<T>(
+        &self,
+        call: &str,
+        arg: &str,
+        result: Result<T>,
+        to_value: impl Fn(&T) -> Value,
+    ) -> Result<T> {
+        let recording = match &result {
+            Ok(value) => Recording {
+                call: call.to_string(),
+                arg: arg.to_string(),
+                response: Some(to_value(value)),
+                error: None,
+                error_kind: None,
+            },
+            Err(e) => {
+                let (error_kind, error) = error_to_recording(e);
+                Recording {
+                    call: call.to_string(),
+                    arg: arg.to_string(),
+                    response: None,
+                    error: Some(error),
+                    error_kind,
+                }
+            }
+        };
+        let line = serde_json::to_string(&recording).unwrap();
+        if let Err(e) = writeln!(self.file.lock().unwrap(), "{}", line) {
+            log::warn!("Unable to write recording - {:?}", e);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for RecordingInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.interface.set_network(network);
+    }
+
+    async fn status(&self) -> Result<()> {
+        let result = self.interface.status().await;
+        // Recorded as true, as a null response is read back as no response
+        self.record("status", "", result, |_| json!(true))
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        let result = self.interface.get_balance(address).await;
+        self.record("get_balance", address, result, balance_to_value)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        let result = self.interface.get_utxo(address).await;
+        self.record("get_utxo", address, result, utxo_to_value)
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        let result = self.interface.broadcast_tx(tx).await;
+        self.record("broadcast_tx", &tx_as_hexstr(tx), result, |x| json!(x))
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        let result = self.interface.get_tx(txid).await;
+        self.record("get_tx", txid, result, |tx| json!(tx_as_hexstr(tx)))
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        let result = self.interface.get_block_headers().await;
+        self.record("get_block_headers", "", result, |x| json!(x))
+    }
+}
+
+/// Blockchain interface that serves the responses recorded in a fixture file
+/// Responses to the same request are served in the order they were recorded,
+/// the last response is repeated once the others have been served.
+pub struct ReplayInterface {
+    recordings: Mutex<HashMap<(String, String), VecDeque<Recording>>>,
+}
+
+impl ReplayInterface {
+    pub fn new(fixture: &str) -> std::io::Result<Self> {
+        let file = File::open(fixture)?;
+        let mut recordings: HashMap<(String, String), VecDeque<Recording>> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording = serde_json::from_str(&line)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            recordings
+                .entry((recording.call.clone(), recording.arg.clone()))
+                .or_default()
+                .push_back(recording);
+        }
+        Ok(ReplayInterface {
+            recordings: Mutex::new(recordings),
+        })
+    }
+
+    /// Return the next recorded response for the call
+    fn replay<T>(
+        &self,
+        call: &str,
+        arg: &str,
+        from_value: impl Fn(&Value) -> Option<T>,
+    ) -> Result<T> {
+        let mut recordings = self.recordings.lock().unwrap();
+        let queue = recordings
+            .get_mut(&(call.to_string(), arg.to_string()))
+            .filter(|queue| !queue.is_empty())
+            .ok_or_else(|| Error::BadData(format!("No recording for {}({})", call, arg)))?;
+        let recording = if queue.len() > 1 {
+            queue.pop_front().unwrap()
+        } else {
+            queue.front().unwrap().clone()
+        };
+        match (recording.response, recording.error) {
+            (Some(response), _) => from_value(&response)
+                .ok_or_else(|| Error::BadData(format!("Invalid recording for {}({})", call, arg))),
+            (None, Some(error)) => Err(recording_to_error(recording.error_kind, error)),
+            // A null response, with no error, is a successful call that returned no data
+            (None, None) => from_value(&Value::Null)
+                .ok_or_else(|| Error::BadData(format!("Invalid recording for {}({})", call, arg))),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for ReplayInterface {
+    fn set_network(&mut self, _network: &Network) {}
+
+    async fn status(&self) -> Result<()> {
+        self.replay("status", "", |_| Some(()))
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        self.replay("get_balance", address, value_to_balance)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        self.replay("get_utxo", address, value_to_utxo)
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        self.replay("broadcast_tx", &tx_as_hexstr(tx), |x| {
+            x.as_str().map(|x| x.to_string())
+        })
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        self.replay("get_tx", txid, value_to_tx)
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        self.replay("get_block_headers", "", |x| {
+            x.as_str().map(|x| x.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chaos_interface::ChaosInterface, config::ChaosConfig,
+        simulated_interface::SimulatedInterface,
+    };
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let fixture = std::env::temp_dir().join("financing_service_recording_test.jsonl");
+        let fixture = fixture.to_str().unwrap();
+        let _ = std::fs::remove_file(fixture);
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let recording = RecordingInterface::new(Box::new(simulated.clone()), fixture).unwrap();
+        let first_balance = recording.get_balance(address).await.unwrap();
+        simulated.fund_address(address, 1000).unwrap();
+        let second_balance = recording.get_balance(address).await.unwrap();
+        let utxo = recording.get_utxo(address).await.unwrap();
+        assert!(recording.get_tx("unknown").await.is_err());
+
+        let replay = ReplayInterface::new(fixture).unwrap();
+        let balance = replay.get_balance(address).await.unwrap();
+        assert_eq!(balance.unconfirmed, first_balance.unconfirmed);
+        let balance = replay.get_balance(address).await.unwrap();
+        assert_eq!(balance.unconfirmed, second_balance.unconfirmed);
+        // Last response is repeated
+        let balance = replay.get_balance(address).await.unwrap();
+        assert_eq!(balance.unconfirmed, second_balance.unconfirmed);
+        let replayed_utxo = replay.get_utxo(address).await.unwrap();
+        assert_eq!(replayed_utxo.len(), utxo.len());
+        assert_eq!(replayed_utxo[0].tx_hash, utxo[0].tx_hash);
+        assert_eq!(replayed_utxo[0].value, 1000);
+        assert!(replay.get_tx("unknown").await.is_err());
+        assert!(replay.status().await.is_err());
+        std::fs::remove_file(fixture).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_status() {
+        let fixture = std::env::temp_dir().join("financing_service_status_test.jsonl");
+        let fixture = fixture.to_str().unwrap();
+        let _ = std::fs::remove_file(fixture);
+
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let recording = RecordingInterface::new(Box::new(simulated), fixture).unwrap();
+        recording.status().await.unwrap();
+
+        let replay = ReplayInterface::new(fixture).unwrap();
+        assert!(replay.status().await.is_ok());
+        assert!(replay.status().await.is_ok());
+        std::fs::remove_file(fixture).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_error_kind() {
+        let fixture = std::env::temp_dir().join("financing_service_error_kind_test.jsonl");
+        let fixture = fixture.to_str().unwrap();
+        let _ = std::fs::remove_file(fixture);
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+
+        // The unavailable interface fails with transport errors
+        let unavailable = ChaosInterface::new(
+            Box::new(SimulatedInterface::new(Network::BSV_Testnet)),
+            &ChaosConfig {
+                error_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        let recording = RecordingInterface::new(Box::new(unavailable), fixture).unwrap();
+        assert!(matches!(
+            recording.get_balance(address).await,
+            Err(Error::IOError(_))
+        ));
+        let simulated = SimulatedInterface::new(Network::BSV_Testnet);
+        let recording = RecordingInterface::new(Box::new(simulated), fixture).unwrap();
+        assert!(matches!(
+            recording.broadcast_tx(&Tx::default()).await,
+            Err(Error::BadData(_))
+        ));
+
+        let replay = ReplayInterface::new(fixture).unwrap();
+        assert!(matches!(
+            replay.get_balance(address).await,
+            Err(Error::IOError(_))
+        ));
+        assert!(matches!(
+            replay.broadcast_tx(&Tx::default()).await,
+            Err(Error::BadData(_))
+        ));
+        std::fs::remove_file(fixture).unwrap();
+    }
+}
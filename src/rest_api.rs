@@ -1,146 +1,616 @@
-use actix_web::{delete, get, http::header::ContentType, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, middleware::DefaultHeaders, post, web, HttpRequest, HttpResponse};
 use async_mutex::Mutex;
 use log::{debug, info};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
 
-use crate::{client::FundRequest, service::Service};
+use crate::{
+    api_error::{ApiError, ErrorCode},
+    arc_interface::ArcTxResponse,
+    auth::{bearer_token, generate_api_key, hash_token},
+    client::{FundRequest, TxQuote},
+    config::{AuthConfig, ClientConfig},
+    openapi::{docs, openapi_json},
+    rest_api_v2::v2_routes,
+    secret::SecretString,
+    service::{FundingResponse, Service, ServiceStatus},
+    tls::ClientCertificate,
+    util::{address_to_locking_script, tx_as_hexstr},
+};
 
 /// Application State Data
 pub struct AppState {
     pub service: Mutex<Service>,
+    /// Token expected with ARC status callbacks
+    pub arc_callback_token: Option<String>,
+    /// If set requests are authenticated
+    pub auth: Option<AuthConfig>,
+    /// Client certificate common names, mapped to the client_id they are allowed to access
+    pub certificate_clients: HashMap<String, String>,
+}
+
+/// Check the request's client certificate and bearer token, if authentication is configured
+/// The admin token is accepted for all requests, a client's API key or mapped client certificate
+/// only for requests about its own client_id. Client management requires the admin token (client_id = None).
+pub(crate) fn authorize(
+    data: &AppState,
+    service: &Service,
+    req: &HttpRequest,
+    client_id: Option<&str>,
+) -> Result<(), ApiError> {
+    authorize_certificate(
+        data,
+        service,
+        req,
+        req.conn_data::<ClientCertificate>(),
+        client_id,
+    )
+}
+
+/// Check the connection's client certificate, if any, and the request's bearer token.
+/// If client certificates are mapped to clients, requests without a mapped certificate require a token
+fn authorize_certificate(
+    data: &AppState,
+    service: &Service,
+    req: &HttpRequest,
+    certificate: Option<&ClientCertificate>,
+    client_id: Option<&str>,
+) -> Result<(), ApiError> {
+    let certificate_client_id =
+        certificate.and_then(|cert| data.certificate_clients.get(&cert.common_name));
+    if let Some(certificate_client_id) = certificate_client_id {
+        if client_id == Some(certificate_client_id.as_str()) {
+            return Ok(());
+        }
+        if data.auth.is_none() {
+            log::warn!(
+                "authorize - client certificate for {} denied access",
+                certificate_client_id
+            );
+            return Err(ApiError::new(
+                ErrorCode::Forbidden,
+                "Not authorized for this request",
+            ));
+        }
+    }
+    let auth = match &data.auth {
+        Some(auth) => auth,
+        None if data.certificate_clients.is_empty() => return Ok(()),
+        None => {
+            log::warn!("authorize - no mapped client certificate");
+            return Err(ApiError::new(
+                ErrorCode::Unauthorized,
+                "Client certificate required",
+            ));
+        }
+    };
+    let token_hash = match bearer_token(req) {
+        Some(token) => hash_token(token),
+        None => {
+            return Err(ApiError::new(
+                ErrorCode::Unauthorized,
+                "Authorization required",
+            ))
+        }
+    };
+    if token_hash == auth.admin_token_hash.to_lowercase() {
+        return Ok(());
+    }
+    match (client_id, service.get_api_key_client(&token_hash)) {
+        (Some(client_id), Some(key_client_id)) if client_id == key_client_id => Ok(()),
+        (_, Some(key_client_id)) => {
+            log::warn!("authorize - client {} denied access", key_client_id);
+            Err(ApiError::new(
+                ErrorCode::Forbidden,
+                "Not authorized for this request",
+            ))
+        }
+        (_, None) => {
+            log::warn!("authorize - invalid token");
+            Err(ApiError::new(ErrorCode::Unauthorized, "Invalid token"))
+        }
+    }
+}
+
+/// Return the JSON body config, which reports invalid request bodies as API errors
+fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _req| {
+        ApiError::new(ErrorCode::InvalidRequest, "Invalid request body")
+            .with_error(err.to_string())
+            .into()
+    })
+}
+
+/// Headers added to version 1 responses, which point callers at version 2
+fn deprecation_headers() -> DefaultHeaders {
+    DefaultHeaders::new()
+        .add(("Deprecation", "true"))
+        .add(("Link", "</v2>; rel=\"successor-version\""))
+}
+
+/// Register the version 1 routes, status is served on all listeners
+fn v1_routes(cfg: &mut web::ServiceConfig, public: bool, admin: bool) {
+    cfg.service(status);
+    if public {
+        cfg.service(balance)
+            .service(get_funds)
+            .service(get_funds_batch)
+            .service(get_funds_quote)
+            .service(get_address);
+    }
+    if admin {
+        cfg.service(add_client).service(delete_client);
+    }
+}
+
+/// Return the route configuration for a listener, serving the client-facing (public)
+/// and/or client management (admin) routes.
+/// Version 1 is served under /v1 and the unversioned paths, version 2 under /v2
+pub fn api_routes(public: bool, admin: bool) -> impl Fn(&mut web::ServiceConfig) + Clone {
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(json_config()).service(index);
+        if public {
+            cfg.service(arc_callback)
+                .service(openapi_json)
+                .configure(docs);
+        }
+        cfg.service(web::scope("/v2").configure(|cfg| v2_routes(cfg, public, admin)))
+            .service(
+                web::scope("/v1")
+                    .wrap(deprecation_headers())
+                    .configure(|cfg| v1_routes(cfg, public, admin)),
+            )
+            .service(
+                web::scope("")
+                    .wrap(deprecation_headers())
+                    .configure(|cfg| v1_routes(cfg, public, admin)),
+            );
+    }
+}
+
+/// Response to requests that return no data
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SuccessResponse {
+    pub status: String,
+}
+
+impl SuccessResponse {
+    fn new() -> Self {
+        SuccessResponse {
+            status: "Success".to_string(),
+        }
+    }
+}
+
+/// Response to a client address request
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AddressResponse {
+    pub address: String,
+}
+
+/// Response to a client balance request
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BalanceResponse {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+}
+
+/// Response to an add client request
+/// The wif and api_key are only returned once, if they were generated by the service
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AddClientResponse {
+    pub status: String,
+    pub client_id: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub wif: Option<SecretString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub api_key: Option<SecretString>,
 }
 
 /// Get Index endpoint
+#[utoipa::path(responses((status = 200, description = "Service name", body = String)))]
 #[get("/")]
 pub async fn index(_data: web::Data<AppState>) -> String {
     "Financing Service REST API".to_string()
 }
 
 /// Get Service Status endpoint
+#[utoipa::path(responses((status = 200, description = "Service status", body = ServiceStatus)))]
 #[get("/status")]
-pub async fn status(data: web::Data<AppState>) -> impl Responder {
+pub async fn status(data: web::Data<AppState>) -> web::Json<ServiceStatus> {
     log::info!("status");
 
     let service = data.service.lock().await;
-    let status = service.get_status();
-    HttpResponse::Ok()
-        .content_type(ContentType::json())
-        .body(status)
+    web::Json(service.get_status())
 }
 
 /// Endpoint to update all the clients, called by ticker every minute
-pub async fn update_clients(data: web::Data<AppState>) -> impl Responder {
+pub async fn update_clients(data: web::Data<AppState>) -> HttpResponse {
     let mut service = data.service.lock().await;
     service.update_balances().await;
-    HttpResponse::Ok()
+    HttpResponse::Ok().finish()
+}
+
+/// Attempt to reconnect to the blockchain, returns true if connected
+/// Called periodically if the service starts in a degraded state
+pub async fn reconnect_blockchain(data: web::Data<AppState>) -> bool {
+    let mut service = data.service.lock().await;
+    if service.is_blockchain_failed() {
+        service.reconnect().await
+    } else {
+        true
+    }
 }
 
 /// This is the /fund API call request
-#[derive(Deserialize, Debug)]
+/// One of locking_script or address (P2PKH) is required
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct FundingRequest {
-    client_id: String,
-    satoshi: u64,
-    no_of_outpoints: u32,
-    multiple_tx: bool,
-    locking_script: String,
+    pub(crate) client_id: String,
+    pub(crate) satoshi: u64,
+    pub(crate) no_of_outpoints: u32,
+    pub(crate) multiple_tx: bool,
+    pub(crate) locking_script: Option<String>,
+    pub(crate) address: Option<String>,
 }
 
 /// Post Fund endpoint
 /// Example:
 ///     curl --header "Content-Type: application/json" \
 ///     --request POST \
-///     --data '{"client_id":"id1","satoshi":"123","no_of_outpoints":1,"multiple_tx":false,"locking_script":"00000"}' \
+///     --data '{"client_id":"id1","satoshi":123,"no_of_outpoints":1,"multiple_tx":false,"locking_script":"00000"}' \
 ///    http://127.0.0.1:8080/fund
-
+/// or with an address in place of the locking_script:
+///     --data '{"client_id":"id1","satoshi":123,"no_of_outpoints":1,"multiple_tx":false,"address":"mfx...G5BK"}'
+#[utoipa::path(
+    request_body = FundingRequest,
+    responses(
+        (status = 200, description = "Funding transactions and outpoints", body = FundingResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+        (status = 409, description = "INSUFFICIENT_FUNDS", body = ApiError),
+        (status = 422, description = "INVALID_REQUEST or WATCH_ONLY_CLIENT", body = ApiError),
+        (status = 502, description = "BROADCAST_FAILED", body = ApiError),
+        (status = 503, description = "BLOCKCHAIN_UNAVAILABLE", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
 #[post("/fund")]
 pub async fn get_funds(
     data: web::Data<AppState>,
+    req: HttpRequest,
     info: web::Json<FundingRequest>,
-) -> impl Responder {
+) -> Result<web::Json<FundingResponse>, ApiError> {
     log::info!("get_funds");
+    let (_, funding_response) = fund(&data, &req, &info).await?;
+    Ok(web::Json(funding_response))
+}
+
+/// Unspent spent by a quoted funding tx
+#[derive(Serialize, Debug, ToSchema)]
+pub struct QuoteInputResponse {
+    pub txid: String,
+    pub vout: u32,
+    pub satoshi: i64,
+}
 
+/// Output of a quoted funding tx
+#[derive(Serialize, Debug, ToSchema)]
+pub struct QuoteOutputResponse {
+    pub satoshi: i64,
+    /// Hex encoded locking script
+    pub locking_script: String,
+}
+
+/// Quoted funding tx, output 0 is the change
+#[derive(Serialize, Debug, ToSchema)]
+pub struct QuoteTxResponse {
+    pub inputs: Vec<QuoteInputResponse>,
+    pub outputs: Vec<QuoteOutputResponse>,
+    pub fee: i64,
+    /// Hex encoded unsigned transaction
+    pub tx: String,
+}
+
+/// /fund/quote response
+/// If the client has insufficient balance sufficient_funds is false and there are no txs
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingQuoteResponse {
+    pub client_id: String,
+    pub sufficient_funds: bool,
+    /// Total fee of the txs
+    pub fee: i64,
+    pub txs: Vec<QuoteTxResponse>,
+}
+
+impl From<&TxQuote> for QuoteTxResponse {
+    fn from(quote: &TxQuote) -> Self {
+        QuoteTxResponse {
+            inputs: quote
+                .inputs
+                .iter()
+                .map(|x| QuoteInputResponse {
+                    txid: x.tx_hash.clone(),
+                    vout: x.tx_pos,
+                    satoshi: x.value,
+                })
+                .collect(),
+            outputs: quote
+                .tx
+                .outputs
+                .iter()
+                .map(|x| QuoteOutputResponse {
+                    satoshi: x.satoshis,
+                    locking_script: hex::encode(&x.lock_script.0),
+                })
+                .collect(),
+            fee: quote.fee(),
+            tx: tx_as_hexstr(&quote.tx),
+        }
+    }
+}
+
+/// Post Fund Quote endpoint
+/// Runs the coin selection and fee calculation for the request, without funding it
+/// Example:
+///     curl --header "Content-Type: application/json" \
+///     --request POST \
+///     --data '{"client_id":"id1","satoshi":123,"no_of_outpoints":1,"multiple_tx":false,"locking_script":"00000"}' \
+///    http://127.0.0.1:8080/fund/quote
+#[utoipa::path(
+    request_body = FundingRequest,
+    responses(
+        (status = 200, description = "Funding quote", body = FundingQuoteResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+        (status = 422, description = "INVALID_REQUEST or WATCH_ONLY_CLIENT", body = ApiError),
+        (status = 503, description = "BLOCKCHAIN_UNAVAILABLE", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/fund/quote")]
+pub async fn get_funds_quote(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<FundingRequest>,
+) -> Result<web::Json<FundingQuoteResponse>, ApiError> {
+    log::info!("get_funds_quote");
+    Ok(web::Json(quote(&data, &req, &info).await?))
+}
+
+/// Check the funding request and quote the funding txs, shared by all API versions
+pub(crate) async fn quote(
+    data: &AppState,
+    req: &HttpRequest,
+    info: &FundingRequest,
+) -> Result<FundingQuoteResponse, ApiError> {
+    let service = data.service.lock().await;
+    let fund_request = check_funding_request(data, &service, req, info)?;
+    let txs: Vec<QuoteTxResponse> = service
+        .quote_funding_txs(&fund_request)
+        .iter()
+        .flatten()
+        .map(QuoteTxResponse::from)
+        .collect();
+    Ok(FundingQuoteResponse {
+        client_id: fund_request.client_id,
+        sufficient_funds: !txs.is_empty(),
+        fee: txs.iter().map(|tx| tx.fee).sum(),
+        txs,
+    })
+}
+
+/// Result of one request in a batch, either the funding or the error
+#[derive(Serialize, ToSchema)]
+pub struct FundingBatchResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding: Option<FundingResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// Batch /fund response, with a result for each request in order
+#[derive(Serialize, ToSchema)]
+pub struct FundingBatchResponse {
+    pub results: Vec<FundingBatchResult>,
+}
+
+/// Post Fund Batch endpoint
+/// Example:
+///     curl --header "Content-Type: application/json" \
+///     --request POST \
+///     --data '[{"client_id":"id1","satoshi":123,"no_of_outpoints":1,"multiple_tx":false,"locking_script":"00000"}]' \
+///    http://127.0.0.1:8080/fund/batch
+#[utoipa::path(
+    request_body = Vec<FundingRequest>,
+    responses(
+        (status = 200, description = "Funding result for each request", body = FundingBatchResponse),
+        (status = 422, description = "INVALID_REQUEST", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/fund/batch")]
+pub async fn get_funds_batch(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<Vec<FundingRequest>>,
+) -> Result<web::Json<FundingBatchResponse>, ApiError> {
+    log::info!("get_funds_batch");
+    let results = fund_batch(&data, &req, &info)
+        .await?
+        .into_iter()
+        .map(|result| match result {
+            Ok((_, funding_response)) => FundingBatchResult {
+                funding: Some(funding_response),
+                error: None,
+            },
+            Err(error) => FundingBatchResult {
+                funding: None,
+                error: Some(error),
+            },
+        })
+        .collect();
+    Ok(web::Json(FundingBatchResponse { results }))
+}
+
+/// Check the funding request and create the funding outpoints, shared by all API versions
+/// Returns the request as passed to the service along with the response
+pub(crate) async fn fund(
+    data: &AppState,
+    req: &HttpRequest,
+    info: &FundingRequest,
+) -> Result<(FundRequest, FundingResponse), ApiError> {
     let mut service = data.service.lock().await;
+    info!("get_funds!");
+    let fund_request = check_funding_request(data, &service, req, info)?;
+
+    let has_sufficent = service.has_sufficent_balance(&fund_request);
+
+    if has_sufficent.is_none() || !has_sufficent.unwrap() {
+        log::info!("insufficient funds!");
+        Err(ApiError::insufficient_funds(&fund_request.client_id))
+    } else {
+        match service.create_funding_outpoints(&fund_request).await {
+            Ok(funding_response) => Ok((fund_request, funding_response)),
+            Err(error) => {
+                debug!("error = {:?}", &error);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Maximum number of requests in a batch funding request
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Check the batch of funding requests and create the funding outpoints, shared by all API versions
+/// The requests are processed under one lock, returning a result for each request
+pub(crate) async fn fund_batch(
+    data: &AppState,
+    req: &HttpRequest,
+    infos: &[FundingRequest],
+) -> Result<Vec<Result<(FundRequest, FundingResponse), ApiError>>, ApiError> {
+    if infos.is_empty() || infos.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("A batch must contain between 1 and {MAX_BATCH_SIZE} requests"),
+        ));
+    }
+    let mut service = data.service.lock().await;
+    let checked: Vec<Result<FundRequest, ApiError>> = infos
+        .iter()
+        .map(|info| check_funding_request(data, &service, req, info))
+        .collect();
+    let fund_requests: Vec<FundRequest> = checked.iter().flatten().cloned().collect();
+    let mut responses = service
+        .create_batch_funding_outpoints(&fund_requests)
+        .await
+        .into_iter();
+    Ok(checked
+        .into_iter()
+        .map(|fund_request| {
+            let fund_request = fund_request?;
+            // There is a response for each checked request, in order
+            let funding_response = responses.next().unwrap()?;
+            Ok((fund_request, funding_response))
+        })
+        .collect())
+}
+
+/// Check the funding request is valid and authorized, returns the request to pass to the service
+fn check_funding_request(
+    data: &AppState,
+    service: &Service,
+    req: &HttpRequest,
+    info: &FundingRequest,
+) -> Result<FundRequest, ApiError> {
+    authorize(data, service, req, Some(&info.client_id))?;
 
     // These local vars are required as the format! strings don't accept '.` in `{}`
     let client_id = &info.client_id;
     let satoshi = info.satoshi;
     let no_of_outpoints = info.no_of_outpoints;
     let multiple_tx = info.multiple_tx;
-    let locking_script = &info.locking_script;
 
-    info!("get_funds!");
     // Request funding outpoints
     // Do all input checks here
+    // Funding is rejected whenever the blockchain is failed, whether at startup or on a later refresh,
+    // as the client's balance and UTXOs may be stale and the funding tx could not be broadcast
+    if service.is_blockchain_failed() {
+        return Err(ApiError::new(
+            ErrorCode::BlockchainUnavailable,
+            "Unable to connect to blockchain, retry later.",
+        ));
+    }
     if !service.is_client_id_valid(client_id) {
-        let response = format!("{{\"description\": \"Unknown client_id {client_id}\"}}");
-        return HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response);
+        return Err(ApiError::unknown_client(client_id));
+    }
+    if service.is_client_watch_only(client_id) {
+        return Err(ApiError::watch_only_client(client_id));
     }
     if satoshi == 0 {
-        let response = format!("{{\"description\": \"Invalid satoshi value '{satoshi}'\"}}");
-        return HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response);
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("Invalid satoshi value '{satoshi}'"),
+        ));
     }
     if no_of_outpoints == 0 {
-        let response =
-            format!("{{\"description\": \"Invalid no_of_outpoints value '{no_of_outpoints}'}}");
-        return HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response);
-    }
-    // Check locking_script can be converted to bytes
-    let decode_locking_script = hex::decode(locking_script);
-    if decode_locking_script.is_err() {
-        let response = format!(
-            "{{\"description\": \"Unable to convert locking_script to bytes '{locking_script}'\"}}"
-        );
-        return HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response);
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("Invalid no_of_outpoints value '{no_of_outpoints}'"),
+        ));
     }
-
-    let locking_script_as_bytes = decode_locking_script.unwrap();
+    let locking_script_as_bytes = match (&info.locking_script, &info.address) {
+        (Some(locking_script), None) => {
+            // Check locking_script can be converted to bytes
+            hex::decode(locking_script).map_err(|_| {
+                ApiError::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Unable to convert locking_script to bytes '{locking_script}'"),
+                )
+            })?
+        }
+        (None, Some(address)) => {
+            // Check the address is for the configured network
+            address_to_locking_script(address, service.get_network())
+                .map_err(|error| ApiError::new(ErrorCode::InvalidRequest, error))?
+        }
+        _ => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "One of locking_script or address is required",
+            ));
+        }
+    };
     debug!("locking_script_as_bytes = {:?}", &locking_script_as_bytes);
 
-    let fund_request = FundRequest {
+    Ok(FundRequest {
         client_id: client_id.to_string(),
         satoshi,
         no_of_outpoints,
         multiple_tx,
         locking_script: locking_script_as_bytes,
-    };
-
-    let has_sufficent = service.has_sufficent_balance(&fund_request);
-
-    if has_sufficent.is_none() || !has_sufficent.unwrap() {
-        log::info!("insufficient funds!");
-        let response =
-            "{\"description\": \"Insufficent client balance to create funding transactions.\"}"
-                .to_string();
-        HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response)
-    } else {
-        match service.create_funding_outpoints(&fund_request).await {
-            Ok(funding_response) => HttpResponse::Ok()
-                .content_type(ContentType::json())
-                .body(funding_response.to_json()),
-            Err(err_str) => {
-                debug!("err_str = {:?}", &err_str);
-                HttpResponse::UnprocessableEntity()
-                    .content_type(ContentType::json())
-                    .body(err_str)
-            }
-        }
-    }
+    })
 }
 
-#[derive(Deserialize, Debug)]
+/// Request to add a client
+/// If no wif is provided but an address or public_key is, then the client is watch-only.
+/// If none are provided the service generates the client's key, which is only
+/// returned if export_wif is set.
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct ClienAddRequest {
     client_id: String,
-    wif: String,
+    #[schema(value_type = Option<String>)]
+    wif: Option<SecretString>,
+    address: Option<String>,
+    public_key: Option<String>,
+    #[serde(default)]
+    export_wif: bool,
+    /// SHA-256 hash (hex) of the client's API key, if authentication is configured
+    /// and this is not provided the service generates the API key
+    api_key_hash: Option<String>,
 }
 
 /// Add client
@@ -149,108 +619,854 @@ pub struct ClienAddRequest {
 ///     --request POST \
 ///     --data '{"client_id":"client15","wif":"cVL...............qWh"}' \
 ///   http://127.0.0.1:8082/client
-/// {"status": "Success"}
+/// {"status": "Success", "client_id": "client15", "address": "mfx...............G5BK"}
+/// Watch-only clients provide an address or public_key instead of the wif:
+///     --data '{"client_id":"client16","address":"mfx...............G5BK"}'
+/// Omit the wif to have the service generate the key:
+///     --data '{"client_id":"client17"}'
+/// If authentication is configured the admin token is required and the generated API key
+/// is returned as "api_key", it is not available from the service after this.
+#[utoipa::path(
+    request_body = ClienAddRequest,
+    responses(
+        (status = 200, description = "Client added", body = AddClientResponse),
+        (status = 409, description = "CLIENT_EXISTS", body = ApiError),
+        (status = 422, description = "INVALID_CLIENT_CONFIG", body = ApiError),
+        (status = 500, description = "KEY_GENERATION_FAILED", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
 #[post("/client")]
 pub async fn add_client(
     data: web::Data<AppState>,
+    req: HttpRequest,
     info: web::Json<ClienAddRequest>,
-) -> impl Responder {
+) -> Result<web::Json<AddClientResponse>, ApiError> {
     let mut service = data.service.lock().await;
+    authorize(&data, &service, &req, None)?;
     // These local vars are required as the format! strings don't accept '.` in `{}`
     let client_id = &info.client_id;
     log::info!("add_client {}", &client_id);
 
     // check to see if client_id already exists
-    if service.is_client_id_valid(client_id) {
+    if service.is_client_id_valid(client_id) || service.is_client_quarantined(client_id) {
         // Return error we already have this client
-        let response = format!("{{\"description\": \"Unknown client_id {client_id}\"}}");
-        HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response)
+        return Err(ApiError::new(
+            ErrorCode::ClientExists,
+            format!("Client {client_id} already exists"),
+        )
+        .with_client_id(client_id));
+    }
+
+    service
+        .validate_api_key_hash(client_id, &info.api_key_hash)
+        .map_err(|error| {
+            log::warn!("add_client failed - {}", &error);
+            ApiError::new(
+                ErrorCode::InvalidClientConfig,
+                "Invalid client configuration",
+            )
+            .with_client_id(client_id)
+            .with_error(error)
+        })?;
+
+    // Generate the client's API key, if required
+    let api_key = match (&data.auth, &info.api_key_hash) {
+        (Some(_), None) => Some(generate_api_key()),
+        _ => None,
+    };
+    let api_key_hash = match &api_key {
+        Some(api_key) => Some(hash_token(api_key.expose_secret())),
+        None => info.api_key_hash.clone(),
+    };
+
+    let wif = if info.wif.is_none() && info.address.is_none() && info.public_key.is_none() {
+        // Generate the client's key
+        let (_, wif) = service
+            .generate_client(client_id, api_key_hash)
+            .map_err(|error| {
+                log::warn!("add_client failed - {}", &error);
+                ApiError::new(ErrorCode::KeyGenerationFailed, error).with_client_id(client_id)
+            })?;
+        // One-time export, the key is not available from the service after this
+        info.export_wif.then_some(wif)
     } else {
         // if not add it
-        service.add_client(client_id, &info.wif);
-
-        let response: String = "{\"status\": \"Success\"}".to_string();
-        HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(response)
-    }
+        let client_config = ClientConfig {
+            client_id: client_id.to_string(),
+            wif_key: info.wif.clone(),
+            address: info.address.clone(),
+            public_key: info.public_key.clone(),
+            api_key_hash,
+        };
+        service.add_client(&client_config).map_err(|error| {
+            log::warn!("add_client failed - {}", &error);
+            ApiError::new(
+                ErrorCode::InvalidClientConfig,
+                "Invalid client configuration",
+            )
+            .with_client_id(client_id)
+            .with_error(error)
+        })?;
+        None
+    };
+    Ok(web::Json(AddClientResponse {
+        status: "Success".to_string(),
+        client_id: client_id.to_string(),
+        address: service.get_address(client_id).unwrap(),
+        wif,
+        api_key,
+    }))
 }
 
 /// Delete client
 /// Example:
 ///     curl -X POST http://127.0.0.1:8080/client/client_1/
+#[utoipa::path(
+    params(("client_id" = String, Path, description = "Client to delete")),
+    responses(
+        (status = 200, description = "Client deleted", body = SuccessResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
 #[delete("/client/{client_id}")]
-pub async fn delete_client(data: web::Data<AppState>, info: web::Path<String>) -> impl Responder {
+pub async fn delete_client(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Path<String>,
+) -> Result<web::Json<SuccessResponse>, ApiError> {
     let mut service = data.service.lock().await;
+    authorize(&data, &service, &req, None)?;
     // These local vars are required as the format! strings don't accept '.` in `{}`
     let client_id: String = info.to_string();
     log::info!("delete_client {}", &client_id);
 
-    // check to see if client_id already exists
-    if service.is_client_id_valid(&client_id) {
+    // check to see if client_id already exists (including quarantined clients)
+    if service.is_client_id_valid(&client_id) || service.is_client_quarantined(&client_id) {
         // if so delete it
         service.delete_client(&client_id);
-
-        let response = "{\"status\": \"Success\"}".to_string();
-        HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(response)
+        Ok(web::Json(SuccessResponse::new()))
     } else {
-        // return error as we already have this client
-        let response = format!("{{\"description\": \"Unknown client_id {client_id} \"}}");
-        HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response)
+        // return error as we don't have this client
+        Err(ApiError::unknown_client(&client_id))
+    }
+}
+
+/// ARC tx status callback
+/// ARC posts tx status updates here if a callback_url is configured
+#[utoipa::path(
+    request_body = ArcTxResponse,
+    responses(
+        (status = 200, description = "Status update received", body = SuccessResponse),
+        (status = 401, description = "UNAUTHORIZED", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/arc/callback")]
+pub async fn arc_callback(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<ArcTxResponse>,
+) -> Result<web::Json<SuccessResponse>, ApiError> {
+    match &data.arc_callback_token {
+        Some(token) if bearer_token(&req) != Some(token.as_str()) => {
+            log::warn!("arc_callback - invalid callback token");
+            return Err(ApiError::new(
+                ErrorCode::Unauthorized,
+                "Invalid callback token",
+            ));
+        }
+        Some(_) => {}
+        // Without a callback token, callbacks require the admin token if authentication
+        // or client certificate mapping is configured
+        None if data.auth.is_some() || !data.certificate_clients.is_empty() => {
+            let service = data.service.lock().await;
+            authorize(&data, &service, &req, None)?;
+        }
+        None => {}
+    }
+
+    let txid = info.txid.clone().unwrap_or_default();
+    match info.tx_status {
+        Some(tx_status) if !tx_status.is_failure() && !tx_status.is_warning() => {
+            log::info!(
+                "arc_callback {} {:?} block {:?} {:?}",
+                &txid,
+                tx_status,
+                info.block_height,
+                info.block_hash
+            );
+        }
+        tx_status => {
+            log::warn!(
+                "arc_callback {} {:?} {}",
+                &txid,
+                tx_status,
+                info.extra_info.clone().unwrap_or_default()
+            );
+        }
     }
+    data.service.lock().await.update_tx_status(&info);
+    Ok(web::Json(SuccessResponse::new()))
 }
 
 /// Get Address for a particular client_id
+#[utoipa::path(
+    params(("client_id" = String, Path, description = "Client")),
+    responses(
+        (status = 200, description = "Client funding address", body = AddressResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
 #[get("/client/{client_id}/address")]
-pub async fn get_address(data: web::Data<AppState>, info: web::Path<String>) -> impl Responder {
+pub async fn get_address(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Path<String>,
+) -> Result<web::Json<AddressResponse>, ApiError> {
     let client_id: String = info.to_string();
     log::info!("get address {}", &client_id);
 
     let service = data.service.lock().await;
+    authorize(&data, &service, &req, Some(&client_id))?;
 
     // Check client_id
-    if !service.is_client_id_valid(&client_id) {
-        let response = format!("{{\"description\": \"Unknown client_id {client_id} \"}}");
-        HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response)
-    } else {
-        let address = service.get_address(&client_id).unwrap();
-        let response = format!("{{\"address\": \"{address}\"}}");
-        HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(response)
+    match service.get_address(&client_id) {
+        Some(address) => Ok(web::Json(AddressResponse { address })),
+        None => Err(ApiError::unknown_client(&client_id)),
     }
 }
 
 /// Get Balance for a particular client_id endpoint
+#[utoipa::path(
+    params(("client_id" = String, Path, description = "Client")),
+    responses(
+        (status = 200, description = "Client balance in satoshi", body = BalanceResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
 #[get("/client/{client_id}/balance")]
-pub async fn balance(data: web::Data<AppState>, info: web::Path<String>) -> impl Responder {
+pub async fn balance(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Path<String>,
+) -> Result<web::Json<BalanceResponse>, ApiError> {
     let client_id: String = info.to_string();
     log::info!("get balance {}", &client_id);
 
     let service = data.service.lock().await;
+    authorize(&data, &service, &req, Some(&client_id))?;
 
     // Check client_id
-    if !service.is_client_id_valid(&client_id) {
-        let response = format!("{{\"description\": \"Unknown client_id {client_id}\"}}");
-        HttpResponse::UnprocessableEntity()
-            .content_type(ContentType::json())
-            .body(response)
-    } else {
-        let balance = service.get_balance(&client_id).unwrap();
-        let confirmed = balance.confirmed;
-        let unconfirmed = balance.unconfirmed;
-
-        let response = format!("{{\"confirmed\": {confirmed}, \"unconfirmed\": {unconfirmed}}}");
-        HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(response)
+    match service.get_balance(&client_id) {
+        Some(balance) => Ok(web::Json(BalanceResponse {
+            confirmed: balance.confirmed,
+            unconfirmed: balance.unconfirmed,
+        })),
+        None => Err(ApiError::unknown_client(&client_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::StatusCode,
+        test::{self, TestRequest},
+        App,
+    };
+
+    use crate::config::{
+        ChaosConfig, ChaosFault, ChaosScriptConfig, Config, SimulatedFundingConfig,
+    };
+
+    const ADMIN_TOKEN: &str = "admin-token";
+    const CLIENT_KEY: &str = "client1-key";
+
+    fn test_config() -> Config {
+        let mut config = Config::default();
+        config.blockchain_interface.interface_type = "simulated".to_string();
+        config.blockchain_interface.network_type = "testnet".to_string();
+        config
+    }
+
+    async fn test_app_state() -> web::Data<AppState> {
+        test_app_state_with(test_config(), None).await
+    }
+
+    async fn test_app_state_with(config: Config, auth: Option<AuthConfig>) -> web::Data<AppState> {
+        web::Data::new(AppState {
+            service: Mutex::new(Service::new(&config).await),
+            arc_callback_token: None,
+            auth,
+            certificate_clients: HashMap::new(),
+        })
+    }
+
+    fn test_auth() -> Option<AuthConfig> {
+        Some(AuthConfig {
+            admin_token_hash: hash_token(ADMIN_TOKEN),
+        })
+    }
+
+    /// Add the bearer token, if any, to the request
+    fn with_token(req: TestRequest, token: Option<&str>) -> TestRequest {
+        match token {
+            Some(token) => req.insert_header(("Authorization", format!("Bearer {token}"))),
+            None => req,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_authorize_admin_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state_with(test_config(), test_auth()).await)
+                .configure(api_routes(true, true)),
+        )
+        .await;
+
+        let requests = || {
+            [
+                TestRequest::get().uri("/v2/client/unknown/balance"),
+                TestRequest::get().uri("/client/unknown/address"),
+                TestRequest::post()
+                    .uri("/v2/fund/quote")
+                    .set_json(serde_json::json!({"client_id": "unknown", "satoshi": 123, "locking_script": "00"})),
+                TestRequest::delete().uri("/v2/client/unknown"),
+            ]
+        };
+        // The admin token is accepted on all routes
+        for req in requests() {
+            assert_eq!(
+                test::call_service(&app, with_token(req, Some(ADMIN_TOKEN)).to_request())
+                    .await
+                    .status(),
+                StatusCode::NOT_FOUND
+            );
+        }
+        for req in requests() {
+            assert_eq!(
+                test::call_service(&app, with_token(req, None).to_request())
+                    .await
+                    .status(),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        for req in requests() {
+            assert_eq!(
+                test::call_service(&app, with_token(req, Some("invalid")).to_request())
+                    .await
+                    .status(),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        // Status does not require a token
+        let req = TestRequest::get().uri("/v2/status");
+        assert_eq!(
+            test::call_service(&app, with_token(req, None).to_request())
+                .await
+                .status(),
+            StatusCode::OK
+        );
+
+        // Without a callback token, ARC callbacks require the admin token
+        let callback = || {
+            TestRequest::post()
+                .uri("/arc/callback")
+                .set_json(serde_json::json!({"txid": "1234", "txStatus": "MINED"}))
+        };
+        assert_eq!(
+            test::call_service(&app, with_token(callback(), None).to_request())
+                .await
+                .status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            test::call_service(&app, with_token(callback(), Some(ADMIN_TOKEN)).to_request())
+                .await
+                .status(),
+            StatusCode::OK
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_authorize_client_key() {
+        let mut config = test_config();
+        config.client = Some(vec![ClientConfig {
+            client_id: "client1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            api_key_hash: Some(hash_token(CLIENT_KEY)),
+            ..Default::default()
+        }]);
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state_with(config, test_auth()).await)
+                .configure(api_routes(true, true)),
+        )
+        .await;
+
+        // The client key is accepted for its own client_id
+        let req = TestRequest::get().uri("/v2/client/client1/balance");
+        assert_eq!(
+            test::call_service(&app, with_token(req, Some(CLIENT_KEY)).to_request())
+                .await
+                .status(),
+            StatusCode::OK
+        );
+        let req = TestRequest::get().uri("/client/client1/address");
+        assert_eq!(
+            test::call_service(&app, with_token(req, Some(CLIENT_KEY)).to_request())
+                .await
+                .status(),
+            StatusCode::OK
+        );
+
+        // But not for another client, or client management
+        for req in [
+            TestRequest::get().uri("/v2/client/client2/balance"),
+            TestRequest::post()
+                .uri("/client")
+                .set_json(serde_json::json!({"client_id": "client3"})),
+            TestRequest::delete().uri("/v2/client/client1"),
+        ] {
+            assert_eq!(
+                test::call_service(&app, with_token(req, Some(CLIENT_KEY)).to_request())
+                    .await
+                    .status(),
+                StatusCode::FORBIDDEN
+            );
+        }
+
+        // Another client can not be added with the same API key hash
+        let req = TestRequest::post()
+            .uri("/v2/client")
+            .set_json(serde_json::json!({
+                "client_id": "client3",
+                "address": "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF",
+                "api_key_hash": hash_token(CLIENT_KEY).to_uppercase(),
+            }));
+        let resp = test::call_service(&app, with_token(req, Some(ADMIN_TOKEN)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "INVALID_CLIENT_CONFIG");
+    }
+
+    #[actix_web::test]
+    async fn test_authorize_client_certificate() {
+        let mut config = test_config();
+        config.client = Some(vec![ClientConfig {
+            client_id: "client1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        }]);
+        let data = AppState {
+            service: Mutex::new(Service::new(&config).await),
+            arc_callback_token: None,
+            auth: None,
+            certificate_clients: HashMap::from([(
+                "client1-cert".to_string(),
+                "client1".to_string(),
+            )]),
+        };
+        let service = data.service.lock().await;
+        let req = TestRequest::default().to_http_request();
+        let certificate = |common_name: &str| ClientCertificate {
+            common_name: common_name.to_string(),
+        };
+        let status = |certificate: Option<&ClientCertificate>, client_id: Option<&str>| {
+            authorize_certificate(&data, &service, &req, certificate, client_id)
+                .map_err(|e| e.code.status_code())
+        };
+
+        // The mapped certificate is accepted for its own client_id
+        let mapped = certificate("client1-cert");
+        assert!(status(Some(&mapped), Some("client1")).is_ok());
+        assert_eq!(
+            status(Some(&mapped), Some("client2")),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(status(Some(&mapped), None), Err(StatusCode::FORBIDDEN));
+
+        // Without a certificate, or with an unmapped certificate, requests are denied
+        let unmapped = certificate("other-cert");
+        for certificate in [None, Some(&unmapped)] {
+            for client_id in [Some("client1"), None] {
+                assert_eq!(
+                    status(certificate, client_id),
+                    Err(StatusCode::UNAUTHORIZED)
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_versioned_routes() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state().await)
+                .configure(api_routes(true, true)),
+        )
+        .await;
+
+        let resp =
+            test::call_service(&app, TestRequest::get().uri("/v2/status").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Deprecation").is_none());
+        for uri in ["/status", "/v1/status"] {
+            let resp = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers().get("Deprecation").unwrap(), "true");
+        }
+
+        // Client management is served alongside the client-facing routes
+        for uri in ["/v2/client/unknown", "/client/unknown"] {
+            let req = TestRequest::delete().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["code"], "UNKNOWN_CLIENT");
+        }
+
+        // Invalid request bodies are reported as API errors
+        let req = TestRequest::post()
+            .uri("/v2/fund")
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(r#"{"client_id":"unknown","satoshi":"123"}"#)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "INVALID_REQUEST");
+
+        // Quotes are checked as funding requests
+        let req = TestRequest::post()
+            .uri("/v2/fund/quote")
+            .set_json(
+                serde_json::json!({"client_id": "unknown", "satoshi": 123, "locking_script": "00"}),
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_fund_batch() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state().await)
+                .configure(api_routes(true, false)),
+        )
+        .await;
+
+        // Each request in the batch has its own result
+        let req = TestRequest::post()
+            .uri("/v2/fund/batch")
+            .set_json(serde_json::json!([
+                {"client_id": "unknown", "satoshi": 123, "locking_script": "00"},
+                {"client_id": "unknown", "satoshi": 0, "locking_script": "00"},
+            ]))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["results"][0]["error"]["code"], "UNKNOWN_CLIENT");
+        assert_eq!(body["results"][1]["error"]["code"], "UNKNOWN_CLIENT");
+        assert_eq!(body["results"].as_array().unwrap().len(), 2);
+
+        let req = TestRequest::post()
+            .uri("/fund/batch")
+            .set_json(serde_json::json!([]))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_quarantined_clients() {
+        // Invalid testnet key (bad checksum) and a mainnet key on testnet
+        let invalid_wif = "cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FH";
+        let mainnet_wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        let mut config = test_config();
+        config.client = Some(vec![
+            ClientConfig {
+                client_id: "client1".to_string(),
+                wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+                ..Default::default()
+            },
+            ClientConfig {
+                client_id: "invalid".to_string(),
+                wif_key: Some(invalid_wif.into()),
+                ..Default::default()
+            },
+            ClientConfig {
+                client_id: "mainnet".to_string(),
+                wif_key: Some(mainnet_wif.into()),
+                ..Default::default()
+            },
+        ]);
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state_with(config, None).await)
+                .configure(api_routes(true, true)),
+        )
+        .await;
+
+        // The bad clients are reported, without their keys, and the service runs with the rest
+        let resp = test::call_service(&app, TestRequest::get().uri("/status").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(!body.contains(invalid_wif));
+        assert!(!body.contains(mainnet_wif));
+        let status: serde_json::Value = serde_json::from_str(body).unwrap();
+        let quarantined = status["quarantined_clients"].as_array().unwrap();
+        assert_eq!(quarantined.len(), 2);
+        assert_eq!(quarantined[0]["client_id"], "invalid");
+        assert_eq!(quarantined[1]["client_id"], "mainnet");
+        for client in quarantined {
+            assert!(client["error"].as_str().unwrap().contains("wif_key"));
+        }
+        let req = TestRequest::get()
+            .uri("/client/client1/address")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::get()
+            .uri("/client/invalid/address")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // A quarantined client can be deleted, and is then no longer reported
+        let req = TestRequest::delete().uri("/client/invalid").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::get().uri("/status").to_request();
+        let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(status["quarantined_clients"].as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_fund_quote() {
+        let mut config = test_config();
+        config.simulated.funding = vec![SimulatedFundingConfig {
+            address: "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF".to_string(),
+            satoshi: 100_000,
+        }];
+        config.client = Some(vec![
+            ClientConfig {
+                client_id: "client1".to_string(),
+                wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+                ..Default::default()
+            },
+            ClientConfig {
+                client_id: "watch".to_string(),
+                address: Some("mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF".to_string()),
+                ..Default::default()
+            },
+        ]);
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state_with(config, None).await)
+                .configure(api_routes(true, false)),
+        )
+        .await;
+        let request = |uri: &str, client_id: &str, satoshi: u64| {
+            TestRequest::post()
+                .uri(uri)
+                .set_json(serde_json::json!({
+                    "client_id": client_id,
+                    "satoshi": satoshi,
+                    "no_of_outpoints": 2,
+                    "multiple_tx": false,
+                    "locking_script": "76a914b467faf0ef536db106d67f872c448bcaccb878c988ac",
+                }))
+                .to_request()
+        };
+
+        // The quote selects the funding unspent, with the change as output 0
+        let quote: serde_json::Value =
+            test::call_and_read_body_json(&app, request("/fund/quote", "client1", 123)).await;
+        assert_eq!(quote["sufficient_funds"], true);
+        assert_eq!(quote["fee"], 750);
+        let txs = quote["txs"].as_array().unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0]["inputs"][0]["satoshi"], 100_000);
+        assert_eq!(txs[0]["outputs"][0]["satoshi"], 100_000 - 2 * 123 - 750);
+        assert_eq!(txs[0]["outputs"][1]["satoshi"], 123);
+        assert_eq!(txs[0]["outputs"].as_array().unwrap().len(), 3);
+
+        // Quoting does not change the client's unspent, so the quote is repeatable
+        let repeat: serde_json::Value =
+            test::call_and_read_body_json(&app, request("/v2/fund/quote", "client1", 123)).await;
+        assert_eq!(repeat, quote);
+        let req = TestRequest::get()
+            .uri("/client/client1/balance")
+            .to_request();
+        let balance: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(balance["unconfirmed"], 100_000);
+
+        // Funding spends the quoted unspent, the next quote spends its change
+        let funding: serde_json::Value =
+            test::call_and_read_body_json(&app, request("/fund", "client1", 123)).await;
+        let quote: serde_json::Value =
+            test::call_and_read_body_json(&app, request("/fund/quote", "client1", 123)).await;
+        assert_eq!(
+            quote["txs"][0]["inputs"][0]["txid"],
+            funding["outpoints"][0]["hash"]
+        );
+
+        // Insufficient funds are reported in the quote, watch-only clients are rejected
+        let quote: serde_json::Value =
+            test::call_and_read_body_json(&app, request("/fund/quote", "client1", 1_000_000)).await;
+        assert_eq!(quote["sufficient_funds"], false);
+        assert_eq!(quote["fee"], 0);
+        assert!(quote["txs"].as_array().unwrap().is_empty());
+        let resp = test::call_service(&app, request("/fund/quote", "watch", 123)).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_degraded_startup() {
+        // The blockchain is unavailable at startup, and reachable on reconnect
+        let mut config = test_config();
+        config.chaos = Some(ChaosConfig {
+            script: vec![ChaosScriptConfig {
+                method: Some("status".to_string()),
+                call: 1,
+                fault: ChaosFault::Error,
+            }],
+            ..Default::default()
+        });
+        config.simulated.funding = vec![SimulatedFundingConfig {
+            address: "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF".to_string(),
+            satoshi: 100_000,
+        }];
+        config.client = Some(vec![ClientConfig {
+            client_id: "client1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        }]);
+        let data = test_app_state_with(config, None).await;
+        assert!(data.service.lock().await.is_blockchain_failed());
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(api_routes(true, false)),
+        )
+        .await;
+        let fund_request = || {
+            TestRequest::post()
+                .uri("/fund")
+                .set_json(serde_json::json!({
+                    "client_id": "client1",
+                    "satoshi": 123,
+                    "no_of_outpoints": 1,
+                    "multiple_tx": false,
+                    "locking_script": "76a914b467faf0ef536db106d67f872c448bcaccb878c988ac",
+                }))
+                .to_request()
+        };
+
+        // Funding is rejected as retryable until the service reconnects
+        let resp = test::call_service(&app, fund_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "10");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "BLOCKCHAIN_UNAVAILABLE");
+
+        assert!(reconnect_blockchain(data.clone()).await);
+        let resp = test::call_service(&app, fund_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["outpoints"].as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_routes() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state().await)
+                .configure(api_routes(false, true)),
+        )
+        .await;
+        let resp = test::call_service(&app, TestRequest::get().uri("/status").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(
+            &app,
+            TestRequest::get()
+                .uri("/v2/client/id1/balance")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = test::read_body(resp).await;
+        assert!(body.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_admin_listener() {
+        let filename =
+            std::env::temp_dir().join(format!("admin_listener_{}.toml", std::process::id()));
+        let mut config = test_config();
+        config.dynamic_config.filename = filename.to_string_lossy().to_string();
+        // The public and admin listeners share the service, as in main
+        let data = test_app_state_with(config, None).await;
+        let public = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(api_routes(true, false)),
+        )
+        .await;
+        let admin = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(api_routes(false, true)),
+        )
+        .await;
+        let add_client = |uri: &str| {
+            TestRequest::post()
+                .uri(uri)
+                .set_json(serde_json::json!({
+                    "client_id": "client2",
+                    "address": "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF",
+                }))
+                .to_request()
+        };
+
+        // Client management is not served on the public listener
+        for uri in ["/client", "/v1/client", "/v2/client"] {
+            let resp = test::call_service(&public, add_client(uri)).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        }
+        let resp = test::call_service(
+            &public,
+            TestRequest::delete().uri("/client/client2").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Clients added on the admin listener are available on the public listener
+        let resp = test::call_service(&admin, add_client("/client")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::get()
+            .uri("/client/client2/address")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&public, req).await;
+        assert_eq!(body["address"], "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF");
+
+        // And removed from it when deleted on the admin listener
+        let resp = test::call_service(
+            &admin,
+            TestRequest::delete().uri("/client/client2").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::get()
+            .uri("/client/client2/address")
+            .to_request();
+        let resp = test::call_service(&public, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        std::fs::remove_file(&filename).unwrap();
     }
 }
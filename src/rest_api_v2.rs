@@ -0,0 +1,244 @@
+use actix_web::{post, web, HttpRequest};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    api_error::ApiError,
+    broadcaster::BroadcastRecord,
+    client::FundRequest,
+    events::events,
+    rest_api::{
+        add_client, balance, delete_client, fund, fund_batch, get_address, quote, status, AppState,
+        FundingQuoteResponse, FundingRequest,
+    },
+    service::FundingResponse,
+    util::tx_as_hexstr,
+};
+
+// Version 2 of the REST API
+// Endpoints whose contract is unchanged share the version 1 handlers
+
+/// Register the version 2 routes, status is served on all listeners
+pub fn v2_routes(cfg: &mut web::ServiceConfig, public: bool, admin: bool) {
+    cfg.service(status);
+    if public {
+        cfg.service(balance)
+            .service(get_funds)
+            .service(get_funds_batch)
+            .service(get_funds_quote)
+            .service(get_address)
+            .service(events);
+    }
+    if admin {
+        cfg.service(add_client).service(delete_client);
+    }
+}
+
+fn default_no_of_outpoints() -> u32 {
+    1
+}
+
+/// Version 2 /fund request
+/// One of locking_script or address (P2PKH) is required
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct FundingRequestV2 {
+    client_id: String,
+    /// Value in satoshi of each funding outpoint
+    satoshi: u64,
+    /// Number of funding outpoints, defaults to 1
+    #[serde(default = "default_no_of_outpoints")]
+    no_of_outpoints: u32,
+    /// If set each outpoint is funded by a separate tx, defaults to false
+    #[serde(default)]
+    multiple_tx: bool,
+    /// Hex encoded locking script of the funding outpoints
+    locking_script: Option<String>,
+    address: Option<String>,
+}
+
+impl From<FundingRequestV2> for FundingRequest {
+    fn from(request: FundingRequestV2) -> Self {
+        FundingRequest {
+            client_id: request.client_id,
+            satoshi: request.satoshi,
+            no_of_outpoints: request.no_of_outpoints,
+            multiple_tx: request.multiple_tx,
+            locking_script: request.locking_script,
+            address: request.address,
+        }
+    }
+}
+
+/// Funding outpoint, with the value and locking script it was created with
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingOutpointV2 {
+    pub txid: String,
+    pub vout: u32,
+    pub satoshi: u64,
+    /// Hex encoded locking script
+    pub locking_script: String,
+}
+
+/// Funding transaction
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingTxV2 {
+    pub txid: String,
+    /// Hex encoded transaction
+    pub tx: String,
+}
+
+/// Version 2 /fund response
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingResponseV2 {
+    pub client_id: String,
+    pub outpoints: Vec<FundingOutpointV2>,
+    pub txs: Vec<FundingTxV2>,
+    /// Per endpoint broadcast results, if broadcast fan-out is configured
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub broadcasts: Vec<BroadcastRecord>,
+}
+
+impl FundingResponseV2 {
+    fn new(fund_request: FundRequest, funding_response: FundingResponse) -> Self {
+        let locking_script = hex::encode(&fund_request.locking_script);
+        let outpoints = funding_response
+            .outpoints
+            .iter()
+            .map(|op| FundingOutpointV2 {
+                txid: op.hash.encode(),
+                vout: op.index,
+                satoshi: fund_request.satoshi,
+                locking_script: locking_script.clone(),
+            })
+            .collect();
+        let txs = funding_response
+            .txs
+            .iter()
+            .map(|tx| FundingTxV2 {
+                txid: tx.hash().encode(),
+                tx: tx_as_hexstr(tx),
+            })
+            .collect();
+        FundingResponseV2 {
+            client_id: fund_request.client_id,
+            outpoints,
+            txs,
+            broadcasts: funding_response.broadcasts,
+        }
+    }
+}
+
+/// Post Fund endpoint (version 2)
+/// Example:
+///     curl --header "Content-Type: application/json" \
+///     --request POST \
+///     --data '{"client_id":"id1","satoshi":123,"address":"mfx...G5BK"}' \
+///    http://127.0.0.1:8080/v2/fund
+#[utoipa::path(
+    request_body = FundingRequestV2,
+    responses(
+        (status = 200, description = "Funding transactions and outpoints", body = FundingResponseV2),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+        (status = 409, description = "INSUFFICIENT_FUNDS", body = ApiError),
+        (status = 422, description = "INVALID_REQUEST or WATCH_ONLY_CLIENT", body = ApiError),
+        (status = 502, description = "BROADCAST_FAILED", body = ApiError),
+        (status = 503, description = "BLOCKCHAIN_UNAVAILABLE", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/fund")]
+pub async fn get_funds(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<FundingRequestV2>,
+) -> Result<web::Json<FundingResponseV2>, ApiError> {
+    log::info!("get_funds_v2");
+    let (fund_request, funding_response) = fund(&data, &req, &info.into_inner().into()).await?;
+    Ok(web::Json(FundingResponseV2::new(
+        fund_request,
+        funding_response,
+    )))
+}
+
+/// Result of one request in a batch, either the funding or the error
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingBatchResultV2 {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding: Option<FundingResponseV2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// Version 2 batch /fund response, with a result for each request in order
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FundingBatchResponseV2 {
+    pub results: Vec<FundingBatchResultV2>,
+}
+
+/// Post Fund Batch endpoint (version 2)
+/// Example:
+///     curl --header "Content-Type: application/json" \
+///     --request POST \
+///     --data '[{"client_id":"id1","satoshi":123,"address":"mfx...G5BK"},{"client_id":"id1","satoshi":456,"address":"mfx...G5BK"}]' \
+///    http://127.0.0.1:8080/v2/fund/batch
+#[utoipa::path(
+    request_body = Vec<FundingRequestV2>,
+    responses(
+        (status = 200, description = "Funding result for each request", body = FundingBatchResponseV2),
+        (status = 422, description = "INVALID_REQUEST", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/fund/batch")]
+pub async fn get_funds_batch(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<Vec<FundingRequestV2>>,
+) -> Result<web::Json<FundingBatchResponseV2>, ApiError> {
+    log::info!("get_funds_batch_v2");
+    let requests: Vec<FundingRequest> = info.into_inner().into_iter().map(Into::into).collect();
+    let results = fund_batch(&data, &req, &requests)
+        .await?
+        .into_iter()
+        .map(|result| match result {
+            Ok((fund_request, funding_response)) => FundingBatchResultV2 {
+                funding: Some(FundingResponseV2::new(fund_request, funding_response)),
+                error: None,
+            },
+            Err(error) => FundingBatchResultV2 {
+                funding: None,
+                error: Some(error),
+            },
+        })
+        .collect();
+    Ok(web::Json(FundingBatchResponseV2 { results }))
+}
+
+/// Post Fund Quote endpoint (version 2)
+/// Runs the coin selection and fee calculation for the request, without funding it
+/// Example:
+///     curl --header "Content-Type: application/json" \
+///     --request POST \
+///     --data '{"client_id":"id1","satoshi":123,"address":"mfx...G5BK"}' \
+///    http://127.0.0.1:8080/v2/fund/quote
+#[utoipa::path(
+    request_body = FundingRequestV2,
+    responses(
+        (status = 200, description = "Funding quote", body = FundingQuoteResponse),
+        (status = 404, description = "UNKNOWN_CLIENT", body = ApiError),
+        (status = 422, description = "INVALID_REQUEST or WATCH_ONLY_CLIENT", body = ApiError),
+        (status = 503, description = "BLOCKCHAIN_UNAVAILABLE", body = ApiError),
+    ),
+    security((), ("bearer" = []))
+)]
+#[post("/fund/quote")]
+pub async fn get_funds_quote(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    info: web::Json<FundingRequestV2>,
+) -> Result<web::Json<FundingQuoteResponse>, ApiError> {
+    log::info!("get_funds_quote_v2");
+    Ok(web::Json(
+        quote(&data, &req, &info.into_inner().into()).await?,
+    ))
+}
@@ -0,0 +1,234 @@
+use std::{collections::HashSet, io::Cursor, sync::Mutex};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use chain_gang::{
+    interface::{Balance, BlockchainInterface, Utxo, UtxoEntry},
+    messages::Tx,
+    network::Network,
+    util::{Error, Result, Serializable},
+};
+
+use crate::{config::RpcConfig, util::tx_as_hexstr};
+
+/// JSON-RPC response
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    result: Option<Value>,
+    error: Option<Value>,
+}
+
+/// Unspent output as returned by listunspent
+#[derive(Debug, Deserialize)]
+struct RpcUnspent {
+    txid: String,
+    vout: u32,
+    amount: f64,
+    confirmations: u32,
+}
+
+/// Blockchain interface that uses a Bitcoin SV node's JSON-RPC interface
+/// Addresses are imported into the node's wallet (as watch-only) the first time they are queried
+pub struct RpcInterface {
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    /// Rescan the blockchain when importing an address
+    rescan: bool,
+    imported_addresses: Mutex<HashSet<String>>,
+    client: reqwest::Client,
+}
+
+impl RpcInterface {
+    pub fn new(url: &str, rpc_config: &RpcConfig) -> Self {
+        RpcInterface {
+            url: url.to_string(),
+            user: rpc_config.user.clone(),
+            password: rpc_config.password.clone(),
+            rescan: rpc_config.rescan,
+            imported_addresses: Mutex::new(HashSet::new()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Call the JSON-RPC method, returning the result
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let mut request = self.client.post(&self.url).json(&json!({
+            "jsonrpc": "1.0",
+            "id": "financing-service",
+            "method": method,
+            "params": params,
+        }));
+        if let Some(user) = &self.user {
+            request = request.basic_auth(user, self.password.as_ref());
+        }
+        // Transport errors are reported as IOError, errors returned by the node as BadData
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+        // Note the node returns error status codes along with the error in the body
+        let rpc_response: RpcResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+        match rpc_response.error {
+            Some(error) if !error.is_null() => {
+                Err(Error::BadData(format!("{} failed - {}", method, error)))
+            }
+            _ => Ok(rpc_response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// Import the address into the node's wallet, if not already imported
+    async fn import_address(&self, address: &str) -> Result<()> {
+        if self.imported_addresses.lock().unwrap().contains(address) {
+            return Ok(());
+        }
+        self.call("importaddress", json!([address, "", self.rescan]))
+            .await?;
+        self.imported_addresses
+            .lock()
+            .unwrap()
+            .insert(address.to_string());
+        Ok(())
+    }
+
+    async fn list_unspent(&self, address: &str) -> Result<Vec<RpcUnspent>> {
+        self.import_address(address).await?;
+        let result = self
+            .call("listunspent", json!([0, 9999999, [address]]))
+            .await?;
+        serde_json::from_value(result).map_err(|e| Error::BadData(e.to_string()))
+    }
+
+    async fn get_block_count(&self) -> Result<u32> {
+        let result = self.call("getblockcount", json!([])).await?;
+        result
+            .as_u64()
+            .map(|height| height as u32)
+            .ok_or_else(|| Error::BadData("getblockcount returned invalid height".to_string()))
+    }
+}
+
+/// Convert BSV amount to satoshi
+fn to_satoshi(amount: f64) -> i64 {
+    (amount * 100_000_000.0).round() as i64
+}
+
+#[async_trait]
+impl BlockchainInterface for RpcInterface {
+    fn set_network(&mut self, _network: &Network) {
+        // The network is determined by the node
+    }
+
+    async fn status(&self) -> Result<()> {
+        self.get_block_count().await.map(|_| ())
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        let unspent = self.list_unspent(address).await?;
+        let mut balance = Balance::default();
+        for entry in unspent {
+            let value = to_satoshi(entry.amount) as u64;
+            if entry.confirmations > 0 {
+                balance.confirmed += value;
+            } else {
+                balance.unconfirmed += value;
+            }
+        }
+        Ok(balance)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        let unspent = self.list_unspent(address).await?;
+        let block_count = self.get_block_count().await?;
+        Ok(unspent
+            .into_iter()
+            .map(|entry| UtxoEntry {
+                height: match entry.confirmations {
+                    0 => 0,
+                    confirmations => block_count + 1 - confirmations,
+                },
+                tx_pos: entry.vout,
+                tx_hash: entry.txid,
+                value: to_satoshi(entry.amount),
+            })
+            .collect())
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        let result = self
+            .call("sendrawtransaction", json!([tx_as_hexstr(tx)]))
+            .await?;
+        Ok(result.as_str().unwrap_or_default().to_string())
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        let result = self.call("getrawtransaction", json!([txid])).await?;
+        let tx_as_bytes = hex::decode(result.as_str().unwrap_or_default())
+            .map_err(|e| Error::BadData(e.to_string()))?;
+        Tx::read(&mut Cursor::new(tx_as_bytes))
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        let hash = self.call("getbestblockhash", json!([])).await?;
+        let header = self.call("getblockheader", json!([hash])).await?;
+        Ok(header.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{post, App, HttpResponse, HttpServer};
+
+    #[post("/")]
+    async fn mock_node(body: String) -> HttpResponse {
+        let request: Value = serde_json::from_str(&body).unwrap();
+        let result =
+            match request["method"].as_str().unwrap() {
+                "importaddress" => Value::Null,
+                "getblockcount" => json!(110),
+                "listunspent" => json!([
+                    {"txid": "aa", "vout": 0, "amount": 0.5, "confirmations": 10},
+                    {"txid": "bb", "vout": 1, "amount": 0.00000123, "confirmations": 0},
+                ]),
+                _ => return HttpResponse::InternalServerError().body(
+                    r#"{"result": null, "error": {"code": -32601, "message": "Method not found"}}"#,
+                ),
+            };
+        HttpResponse::Ok().json(json!({"result": result, "error": null}))
+    }
+
+    /// Start a mock node, returning its url
+    fn start_mock_node() -> String {
+        let server = HttpServer::new(|| App::new().service(mock_node))
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        format!("http://{}/", addr)
+    }
+
+    #[actix_web::test]
+    async fn test_rpc_utxo_and_balance() {
+        let url = start_mock_node();
+        let interface = RpcInterface::new(&url, &RpcConfig::default());
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+
+        let balance = interface.get_balance(address).await.unwrap();
+        assert_eq!(balance.confirmed, 50_000_000);
+        assert_eq!(balance.unconfirmed, 123);
+
+        let utxo = interface.get_utxo(address).await.unwrap();
+        assert_eq!(utxo[0].height, 101);
+        assert_eq!(utxo[0].value, 50_000_000);
+        assert_eq!(utxo[1].height, 0);
+        assert_eq!(utxo[1].tx_pos, 1);
+
+        assert!(interface.get_block_headers().await.is_err());
+    }
+}
@@ -1,24 +1,40 @@
-use serde::Serialize;
-use std::time::SystemTime;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use utoipa::ToSchema;
 
 use chain_gang::{
     interface::{Balance, BlockchainInterface},
     messages::{OutPoint, Tx},
+    network::Network,
     util::Hash256,
 };
 use chrono::prelude::DateTime;
 use chrono::Utc;
 
 use crate::{
-    blockchain_factory::blockchain_factory,
-    client::{Client, FundRequest},
+    api_error::{ApiError, ErrorCode},
+    arc_interface::{ArcTxResponse, ArcTxStatus},
+    blockchain_factory::{blockchain_factory, broadcaster_factory, circuit_breaker_config},
+    broadcaster::{BroadcastRecord, Broadcaster},
+    caching_interface::SharedAddressCache,
+    circuit_breaker::{
+        CircuitBreaker, CircuitBreakerInterface, CircuitState, SharedCircuitBreaker,
+    },
+    client::{Client, FundRequest, TxQuote},
     config::{ClientConfig, Config},
     dynamic_config::DynamicConfig,
-    util::tx_as_hexstr,
+    events::{EventBus, ServiceEvent},
+    failover_interface::ActiveBackend,
+    secret::SecretString,
+    util::{generate_wif, tx_as_hexstr},
 };
 
 /// Blockchain Connection Status
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
 pub enum BlockchainConnectionStatus {
     /// Unknown - Starting state of the service
     Unknown,
@@ -28,53 +44,115 @@ pub enum BlockchainConnectionStatus {
     Connected,
 }
 
-#[derive(Clone, Default)]
+/// A client that failed to load from the config and so is not available
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct QuarantinedClient {
+    pub client_id: String,
+    pub error: String,
+}
+
+/// Funding outpoint, as returned by the REST API
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct OutPointResponse {
+    pub hash: String,
+    pub index: u32,
+}
+
+/// Funding transaction, as returned by the REST API
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct TxResponse {
+    /// The hex encoded transaction
+    pub tx: String,
+}
+
+fn serialize_outpoints<S: Serializer>(outpoints: &[OutPoint], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(outpoints.iter().map(|op| OutPointResponse {
+        hash: op.hash.encode(),
+        index: op.index,
+    }))
+}
+
+fn serialize_txs<S: Serializer>(txs: &[Tx], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(txs.iter().map(|tx| TxResponse {
+        tx: tx_as_hexstr(tx),
+    }))
+}
+
+#[derive(Clone, Default, Serialize, ToSchema)]
 pub struct FundingResponse {
+    #[serde(serialize_with = "serialize_outpoints")]
+    #[schema(value_type = Vec<OutPointResponse>)]
     pub outpoints: Vec<OutPoint>,
+    #[serde(serialize_with = "serialize_txs")]
+    #[schema(value_type = Vec<TxResponse>)]
     pub txs: Vec<Tx>,
+    /// Per endpoint broadcast results, if broadcast fan-out is configured
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub broadcasts: Vec<BroadcastRecord>,
 }
 
 impl FundingResponse {
-    /// Given outpoints return them as a string
-    fn outpoints_to_json(&self) -> String {
-        let mut retval: String = "[".to_string();
-
-        for (i, op) in self.outpoints.iter().enumerate() {
-            retval += format!(
-                "{{\"hash\": \"{}\", \"index\": {}}}",
-                op.hash.encode(),
-                op.index
-            )
-            .as_str();
-            if i + 1 != self.outpoints.len() {
-                retval += ",";
-            }
-        }
-        retval += "]";
-        retval
+    /// Return the broadcast failure error
+    fn broadcast_error(&self) -> ApiError {
+        ApiError::new(
+            ErrorCode::BroadcastFailed,
+            "Failed to broadcast funding transaction.",
+        )
+        .with_broadcasts(self.broadcasts.clone())
     }
+}
 
-    fn txs_to_json(&self) -> String {
-        //                 let tx_as_str = tx_as_hexstr(&a_tx);
-        let mut retval: String = "[".to_string();
+/// Service status, as returned by the REST API
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ServiceStatus {
+    pub version: String,
+    pub blockchain_status: BlockchainConnectionStatus,
+    pub blockchain_update_time: String,
+    pub active_backend: String,
+    pub circuit_breaker: CircuitState,
+    pub quarantined_clients: Vec<QuarantinedClient>,
+}
 
-        for (i, tx) in self.txs.iter().enumerate() {
-            retval += format!("{{\"tx\": \"{}\"}}", tx_as_hexstr(tx)).as_str();
-            if i + 1 != self.txs.len() {
-                retval += ",";
-            }
+/// Maximum number of broadcast funding txs tracked for confirmation
+const MAX_TRACKED_TXS: usize = 1000;
+
+/// Broadcast funding tx awaiting confirmation
+struct TrackedTx {
+    txid: String,
+    /// The client that funded the tx
+    client_id: String,
+    /// Txids of the txs whose outputs the tx spends
+    parents: Vec<String>,
+}
+
+/// Return the txids of the tracked txs that are confirmed, with the block height if known.
+/// A tx is confirmed if it has a confirmed output, or if a confirmed tx spends its outputs,
+/// as its change may be spent by the next funding tx before it is mined.
+fn confirmed_txs(
+    tracked: &[&TrackedTx],
+    get_height: impl Fn(&str) -> Option<u32>,
+) -> HashMap<String, Option<u64>> {
+    let mut confirmed: HashMap<String, Option<u64>> = HashMap::new();
+    let mut pending: Vec<&TrackedTx> = Vec::new();
+    for tx in tracked {
+        if let Some(height) = get_height(&tx.txid) {
+            confirmed.insert(tx.txid.clone(), Some(height.into()));
+            pending.push(tx);
         }
-        retval += "]";
-        retval
     }
-
-    pub fn to_json(&self) -> String {
-        format!(
-            "{{\"outpoints\":  {}, \"txs\": {}}}",
-            self.outpoints_to_json(),
-            self.txs_to_json()
-        )
+    // The parents of a confirmed tx are mined in the same or an earlier block
+    while let Some(tx) = pending.pop() {
+        for parent in tracked
+            .iter()
+            .filter(|parent| tx.parents.contains(&parent.txid))
+        {
+            if !confirmed.contains_key(&parent.txid) {
+                confirmed.insert(parent.txid.clone(), None);
+                pending.push(parent);
+            }
+        }
     }
+    confirmed
 }
 
 /// Service data
@@ -83,69 +161,224 @@ pub struct Service {
     blockchain_status: BlockchainConnectionStatus,
     blockchain_update_time: Option<SystemTime>,
     blockchain_interface: Box<dyn BlockchainInterface>,
+    broadcaster: Option<Broadcaster>,
+    active_backend: ActiveBackend,
+    circuit_breaker: SharedCircuitBreaker,
+    /// Cached balances and UTXOs, invalidated when the subscription reports that they have changed
+    address_cache: SharedAddressCache,
+    network: Network,
     clients: Vec<Client>,
+    quarantined_clients: Vec<QuarantinedClient>,
+    /// Client API key hashes, mapped to the client_id
+    api_key_hashes: HashMap<String, String>,
     dynamic_config: DynamicConfig,
+    events: EventBus,
+    /// Broadcast funding txs awaiting confirmation
+    funding_txs: VecDeque<TrackedTx>,
+}
+
+/// Return the client's API key hash as lowercase hex, if it has one.
+/// Returns an error if the hash is not a SHA-256 hex string or is already used by another client
+fn check_api_key_hash(
+    api_key_hashes: &HashMap<String, String>,
+    client_id: &str,
+    api_key_hash: &Option<String>,
+) -> Result<Option<String>, String> {
+    let api_key_hash = match api_key_hash {
+        Some(api_key_hash) => api_key_hash.to_lowercase(),
+        None => return Ok(None),
+    };
+    if api_key_hash.len() != 64 || !api_key_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            r#"Client api_key_hash must be a SHA-256 hash of 64 hex characters (client_id = "{}")."#,
+            client_id
+        ));
+    }
+    if api_key_hashes.contains_key(&api_key_hash) {
+        return Err(format!(
+            r#"Client api_key_hash is already in use by another client (client_id = "{}")."#,
+            client_id
+        ));
+    }
+    Ok(Some(api_key_hash))
 }
 
 impl Service {
     /// Create a new Service from the provided config
     pub async fn new(config: &Config) -> Service {
         let mut clients: Vec<Client> = Vec::new();
-        let blockchain_interface = blockchain_factory(config);
-
-        // Check we can connect to blockchain
-        blockchain_interface
-            .status()
-            .await
-            .expect("Unable to connect to blockchain, ensure that the service is running.");
-
-        if let Some(clients_config) = &config.client {
-            for client_config in clients_config {
-                let new_client = Client::new(client_config);
-                clients.push(new_client);
+        let mut quarantined_clients: Vec<QuarantinedClient> = Vec::new();
+        let mut api_key_hashes: HashMap<String, String> = HashMap::new();
+        let active_backend: ActiveBackend = Arc::new(Mutex::new(None));
+        let address_cache = SharedAddressCache::default();
+        let circuit_breaker: SharedCircuitBreaker =
+            Arc::new(Mutex::new(CircuitBreaker::new(&config.circuit_breaker)));
+        let blockchain_interface = Box::new(CircuitBreakerInterface::new(
+            blockchain_factory(config, &active_backend, &address_cache),
+            &circuit_breaker_config(config),
+            circuit_breaker.clone(),
+        ));
+        let broadcaster = broadcaster_factory(config);
+        let network = config.get_network().unwrap();
+
+        // Check we can connect to blockchain, if not start in a degraded state
+        let connected = match blockchain_interface.status().await {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!(
+                    "Unable to connect to blockchain, starting in degraded state - {:?}",
+                    e
+                );
+                false
             }
-        }
+        };
 
-        // Add the dynamic clients
+        // Add the static and dynamic clients, quarantining any that fail to load
         let dynamic_config = DynamicConfig::new(config);
-        for client_config in &dynamic_config.contents.clients {
-            let new_client = Client::new(client_config);
-            clients.push(new_client);
+        let clients_config = config.client.iter().flatten();
+        for client_config in clients_config.chain(&dynamic_config.contents.clients) {
+            let api_key_hash = check_api_key_hash(
+                &api_key_hashes,
+                &client_config.client_id,
+                &client_config.api_key_hash,
+            );
+            let new_client = api_key_hash.and_then(|api_key_hash| {
+                Client::new(client_config, network).map(|client| (client, api_key_hash))
+            });
+            match new_client {
+                Ok((new_client, api_key_hash)) => {
+                    if let Some(api_key_hash) = api_key_hash {
+                        api_key_hashes.insert(api_key_hash, new_client.client_id.clone());
+                    }
+                    clients.push(new_client);
+                }
+                Err(error) => {
+                    log::error!("Unable to load client - {}", &error);
+                    quarantined_clients.push(QuarantinedClient {
+                        client_id: client_config.client_id.clone(),
+                        error,
+                    });
+                }
+            }
         }
 
         let mut service = Service {
             blockchain_status: BlockchainConnectionStatus::Unknown,
             blockchain_update_time: None,
             blockchain_interface,
+            broadcaster,
+            active_backend,
+            circuit_breaker,
+            address_cache,
+            network,
             clients,
+            quarantined_clients,
+            api_key_hashes,
             dynamic_config,
+            events: EventBus::new(),
+            funding_txs: VecDeque::new(),
         };
-        service.update_balances().await;
+        if connected {
+            service.update_balances().await;
+        } else {
+            service.set_blockchain_status(BlockchainConnectionStatus::Failed);
+        }
         service
     }
 
-    pub fn add_client(&mut self, client_id: &str, wif: &str) {
-        let client_config = ClientConfig {
-            client_id: client_id.to_string(),
-            wif_key: wif.to_string(),
-        };
-        let new_client = Client::new(&client_config);
+    /// Return true if the service has failed to connect to the blockchain
+    pub fn is_blockchain_failed(&self) -> bool {
+        matches!(self.blockchain_status, BlockchainConnectionStatus::Failed)
+    }
+
+    /// Attempt to reconnect to the blockchain, returns true if connected
+    pub async fn reconnect(&mut self) -> bool {
+        match self.blockchain_interface.status().await {
+            Ok(()) => {
+                log::info!("Reconnected to blockchain");
+                self.update_balances().await;
+            }
+            Err(e) => {
+                log::warn!("reconnect - failed {:?}", e);
+                self.set_blockchain_status(BlockchainConnectionStatus::Failed);
+            }
+        }
+        !self.is_blockchain_failed()
+    }
+
+    /// Add a client, returns an error if the client config is not valid
+    pub fn add_client(&mut self, client_config: &ClientConfig) -> Result<(), String> {
+        let api_key_hash = check_api_key_hash(
+            &self.api_key_hashes,
+            &client_config.client_id,
+            &client_config.api_key_hash,
+        )?;
+        let new_client = Client::new(client_config, self.network)?;
+        if let Some(api_key_hash) = api_key_hash {
+            self.api_key_hashes
+                .insert(api_key_hash, new_client.client_id.clone());
+        }
         self.clients.push(new_client);
         // save dynamic info
-        self.dynamic_config.add(&client_config);
+        self.dynamic_config.add(client_config);
+        self.events.publish(ServiceEvent::ClientAdded {
+            client_id: client_config.client_id.clone(),
+        });
+        Ok(())
+    }
+
+    /// Return an error if the API key hash can not be used for the client
+    pub fn validate_api_key_hash(
+        &self,
+        client_id: &str,
+        api_key_hash: &Option<String>,
+    ) -> Result<(), String> {
+        check_api_key_hash(&self.api_key_hashes, client_id, api_key_hash).map(|_| ())
+    }
+
+    /// Add a client with a newly generated key, returns the client's funding address and key
+    pub fn generate_client(
+        &mut self,
+        client_id: &str,
+        api_key_hash: Option<String>,
+    ) -> Result<(String, SecretString), String> {
+        self.validate_api_key_hash(client_id, &api_key_hash)?;
+        // A random value is only an invalid key if it is zero or not less than the curve order,
+        // which is very unlikely (less than 1 in 2^127), so retry rather than fail if it happens
+        for _ in 0..3 {
+            let client_config = ClientConfig {
+                client_id: client_id.to_string(),
+                wif_key: Some(generate_wif(self.network)),
+                api_key_hash: api_key_hash.clone(),
+                ..Default::default()
+            };
+            if self.add_client(&client_config).is_ok() {
+                let address = self.get_address(client_id).unwrap();
+                return Ok((address, client_config.wif_key.unwrap()));
+            }
+        }
+        Err(format!(
+            r#"Unable to generate key (client_id = "{}")."#,
+            client_id
+        ))
     }
 
     pub fn delete_client(&mut self, client_id: &str) {
-        // let new_client = Client::new(&client_config, self.network);
         if let Some(index) = self.clients.iter().position(|c| c.client_id == client_id) {
             self.clients.remove(index);
+            self.events.publish(ServiceEvent::ClientRemoved {
+                client_id: client_id.to_string(),
+            });
         }
+        self.quarantined_clients
+            .retain(|c| c.client_id != client_id);
+        self.api_key_hashes.retain(|_, id| id != client_id);
         // save dynamic info
         self.dynamic_config.remove(client_id);
     }
 
-    /// Return the Service status as a JSON string
-    pub fn get_status(&self) -> String {
+    /// Return the Service status
+    pub fn get_status(&self) -> ServiceStatus {
         let update_time = match self.blockchain_update_time {
             Some(time) => {
                 let datetime = DateTime::<Utc>::from(time);
@@ -153,22 +386,44 @@ impl Service {
             }
             None => "None".to_string(),
         };
-        let version = env!("CARGO_PKG_VERSION");
-        format!(
-            "{{\"version\": \"{}\", \"blockchain_status\": \"{:?}\", \"blockchain_update_time\": \"{}\"}}",
-            version, self.blockchain_status, update_time
-        )
+        let active_backend = match self.active_backend.lock().unwrap().as_ref() {
+            Some(name) => name.clone(),
+            None => "None".to_string(),
+        };
+        ServiceStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            blockchain_status: self.blockchain_status,
+            blockchain_update_time: update_time,
+            active_backend,
+            circuit_breaker: self.circuit_breaker.lock().unwrap().get_state(),
+            quarantined_clients: self.quarantined_clients.clone(),
+        }
+    }
+
+    /// Subscribe to the service events
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<ServiceEvent> {
+        self.events.subscribe()
+    }
+
+    /// Record the blockchain status, publishing an event if it has changed
+    fn set_blockchain_status(&mut self, blockchain_status: BlockchainConnectionStatus) {
+        if self.blockchain_status != blockchain_status {
+            self.events
+                .publish(ServiceEvent::BlockchainStatusChanged { blockchain_status });
+        }
+        self.blockchain_status = blockchain_status;
+        self.blockchain_update_time = Some(SystemTime::now());
     }
 
     async fn get_block_headers(&mut self) {
-        self.blockchain_status = match self.blockchain_interface.get_block_headers().await {
+        let status = match self.blockchain_interface.get_block_headers().await {
             Ok(_) => BlockchainConnectionStatus::Connected,
             Err(e) => {
                 log::warn!("get_block_headers - failed {:?}", e);
                 BlockchainConnectionStatus::Failed
             }
         };
-        self.blockchain_update_time = Some(SystemTime::now());
+        self.set_blockchain_status(status);
     }
 
     /// Update client balances
@@ -178,18 +433,147 @@ impl Service {
             self.get_block_headers().await;
         } else {
             // Get client balances
-            for client in &mut self.clients {
-                self.blockchain_status =
-                    match client.update_balance(&*self.blockchain_interface).await {
-                        Ok(_) => BlockchainConnectionStatus::Connected,
-                        Err(e) => {
-                            log::warn!("update_balance - failed {:?}", e);
-                            BlockchainConnectionStatus::Failed
-                        }
-                    };
-                self.blockchain_update_time = Some(SystemTime::now());
+            self.update_balances_where(|_| true).await;
+        }
+    }
+
+    /// Update the balances of the clients with the given addresses
+    /// The addresses' cached balances and UTXOs are invalidated, as they are known to have changed
+    pub async fn update_client_balances(&mut self, addresses: &[String]) {
+        self.address_cache.invalidate(addresses);
+        self.update_balances_where(|client| addresses.contains(&client.get_address()))
+            .await;
+    }
+
+    /// Update the balances of the clients with unconfirmed funds, as a new block may confirm them
+    pub async fn update_unconfirmed_balances(&mut self) {
+        let addresses: Vec<String> = self
+            .clients
+            .iter()
+            .filter(|client| client.get_balance().unconfirmed != 0)
+            .map(|client| client.get_address())
+            .collect();
+        self.update_client_balances(&addresses).await;
+    }
+
+    /// Return the addresses of the clients
+    pub fn get_addresses(&self) -> Vec<String> {
+        self.clients
+            .iter()
+            .map(|client| client.get_address())
+            .collect()
+    }
+
+    /// Update the balances of the clients that match the predicate
+    async fn update_balances_where(&mut self, predicate: impl Fn(&Client) -> bool) {
+        let mut status = None;
+        let mut events: Vec<ServiceEvent> = Vec::new();
+        for client in self.clients.iter_mut().filter(|client| predicate(client)) {
+            let previous = client.get_balance();
+            status = match client.update_balance(&*self.blockchain_interface).await {
+                Ok(_) => Some(BlockchainConnectionStatus::Connected),
+                Err(e) => {
+                    log::warn!("update_balance - failed {:?}", e);
+                    Some(BlockchainConnectionStatus::Failed)
+                }
+            };
+            let balance = client.get_balance();
+            if balance.confirmed != previous.confirmed
+                || balance.unconfirmed != previous.unconfirmed
+            {
+                events.push(ServiceEvent::BalanceUpdated {
+                    client_id: client.client_id.clone(),
+                    confirmed: balance.confirmed,
+                    unconfirmed: balance.unconfirmed,
+                });
             }
+            // Funding txs are confirmed once their change output, or a tx spending it, is mined
+            let tracked: Vec<&TrackedTx> = self
+                .funding_txs
+                .iter()
+                .filter(|tx| tx.client_id == client.client_id)
+                .collect();
+            let confirmed = confirmed_txs(&tracked, |txid| client.get_confirmed_height(txid));
+            self.funding_txs.retain(|tx| match confirmed.get(&tx.txid) {
+                Some(block_height) if tx.client_id == client.client_id => {
+                    events.push(ServiceEvent::TxConfirmed {
+                        client_id: Some(tx.client_id.clone()),
+                        txid: tx.txid.clone(),
+                        block_height: *block_height,
+                    });
+                    false
+                }
+                _ => true,
+            });
+        }
+        if let Some(status) = status {
+            self.set_blockchain_status(status);
+        }
+        for event in events {
+            self.events.publish(event);
+        }
+    }
+
+    /// Record an ARC tx status callback, publishing an event if the tx has been mined or rejected
+    /// Callbacks without a txStatus are ignored
+    pub fn update_tx_status(&mut self, tx_status: &ArcTxResponse) {
+        let txid = tx_status.txid.clone().unwrap_or_default();
+        let Some(status) = tx_status.tx_status else {
+            log::warn!("ARC callback for txid {} without a txStatus ignored", txid);
+            return;
+        };
+        let mined = matches!(status, ArcTxStatus::Mined | ArcTxStatus::Confirmed);
+        let failed = status.is_failure();
+        if !mined && !failed {
+            return;
         }
+        let client_id = self
+            .funding_txs
+            .iter()
+            .position(|tx| tx.txid == txid)
+            .and_then(|index| self.funding_txs.remove(index))
+            .map(|tx| tx.client_id);
+        let event = if mined {
+            ServiceEvent::TxConfirmed {
+                client_id,
+                txid,
+                block_height: tx_status.block_height,
+            }
+        } else {
+            ServiceEvent::BroadcastFailed {
+                client_id,
+                txid,
+                reason: tx_status.extra_info.clone().unwrap_or_default(),
+            }
+        };
+        self.events.publish(event);
+    }
+
+    /// Track the broadcast funding txs, so that their confirmation can be published
+    fn track_funding_txs(&mut self, client_id: &str, txs: &[Tx]) {
+        for tx in txs {
+            let txid = tx.hash().encode();
+            // Combined batch txs are shared by several requests
+            if !self.funding_txs.iter().any(|tracked| tracked.txid == txid) {
+                self.funding_txs.push_back(TrackedTx {
+                    txid,
+                    client_id: client_id.to_string(),
+                    parents: tx
+                        .inputs
+                        .iter()
+                        .map(|input| input.prev_output.hash.encode())
+                        .collect(),
+                });
+            }
+        }
+        while self.funding_txs.len() > MAX_TRACKED_TXS {
+            self.funding_txs.pop_front();
+        }
+    }
+
+    /// Return the client_id that the API key hash belongs to
+    pub fn get_api_key_client(&self, api_key_hash: &str) -> Option<&String> {
+        self.api_key_hashes.get(api_key_hash)
     }
 
     /// Given a client_id return true if it is valid
@@ -197,6 +581,25 @@ impl Service {
         self.clients.iter().any(|x| x.client_id == client_id)
     }
 
+    /// Return the network the service is configured for
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    /// Given a client_id return true if it failed to load and has been quarantined
+    pub fn is_client_quarantined(&self, client_id: &str) -> bool {
+        self.quarantined_clients
+            .iter()
+            .any(|x| x.client_id == client_id)
+    }
+
+    /// Given a client_id return true if it is a watch-only client
+    pub fn is_client_watch_only(&self, client_id: &str) -> bool {
+        self.clients
+            .iter()
+            .any(|x| x.client_id == client_id && x.is_watch_only())
+    }
+
     /// Given a client_id return the associated balance as JSON string
     pub fn get_balance(&self, client_id: &str) -> Option<Balance> {
         let client = self.clients.iter().find(|x| x.client_id == client_id)?;
@@ -217,6 +620,19 @@ impl Service {
         client.has_sufficent_balance(fund_request)
     }
 
+    /// Quote the funding txs for the request, without changing the client's unspent or broadcasting
+    /// Returns None if the client has insufficient balance
+    pub fn quote_funding_txs(&self, fund_request: &FundRequest) -> Option<Vec<TxQuote>> {
+        if !self.has_sufficent_balance(fund_request)? {
+            return None;
+        }
+        let client = self
+            .clients
+            .iter()
+            .find(|x| x.client_id == fund_request.client_id)?;
+        client.quote_funding_txs(fund_request)
+    }
+
     /// Given txid and no_of_outpoints return the outpoints as JSON string
     fn get_outpoints(&self, hash: Hash256, no_of_outpoints: u32) -> Vec<OutPoint> {
         (1..no_of_outpoints + 1)
@@ -224,16 +640,143 @@ impl Service {
             .collect()
     }
 
+    /// Broadcast the tx, using the broadcaster if configured, returns true if successful
+    async fn broadcast_tx(&self, tx: &Tx) -> (bool, Option<BroadcastRecord>) {
+        match &self.broadcaster {
+            Some(broadcaster) => {
+                let record = broadcaster.broadcast(tx).await;
+                (record.accepted, Some(record))
+            }
+            None => (
+                self.blockchain_interface.broadcast_tx(tx).await.is_ok(),
+                None,
+            ),
+        }
+    }
+
+    /// Create the funding outpoints for a batch of requests, returning a result for each request
+    /// Single tx requests for the same client are combined into one tx, if the client has an
+    /// unspent large enough to fund them, otherwise each request is funded separately
+    pub async fn create_batch_funding_outpoints(
+        &mut self,
+        fund_requests: &[FundRequest],
+    ) -> Vec<Result<FundingResponse, ApiError>> {
+        let mut results: Vec<Option<Result<FundingResponse, ApiError>>> =
+            fund_requests.iter().map(|_| None).collect();
+
+        // Group the single tx requests by client, preserving the request order
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (index, fund_request) in fund_requests.iter().enumerate() {
+            if fund_request.no_of_outpoints > 1 && fund_request.multiple_tx {
+                continue;
+            }
+            match groups
+                .iter_mut()
+                .find(|(client_id, _)| *client_id == fund_request.client_id)
+            {
+                Some((_, indices)) => indices.push(index),
+                None => groups.push((&fund_request.client_id, vec![index])),
+            }
+        }
+
+        for (client_id, indices) in groups.iter().filter(|(_, indices)| indices.len() > 1) {
+            let requests: Vec<FundRequest> =
+                indices.iter().map(|i| fund_requests[*i].clone()).collect();
+            let client: &mut Client =
+                match self.clients.iter_mut().find(|x| x.client_id == *client_id) {
+                    Some(client) => client,
+                    None => {
+                        for i in indices {
+                            results[*i] = Some(Err(ApiError::unknown_client(client_id)));
+                        }
+                        continue;
+                    }
+                };
+            let tx = match client.create_combined_funding_tx(&requests) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            log::info!("tx_as_str = {}", tx_as_hexstr(&tx));
+
+            let (broadcast_ok, record) = self.broadcast_tx(&tx).await;
+            if !broadcast_ok {
+                log::info!("Failed to broadcast funding transaction");
+                self.publish_broadcast_failed(client_id, &tx);
+            }
+            // Change is output 0, followed by each request's outpoints
+            let mut index = 1;
+            for (i, fund_request) in indices.iter().zip(&requests) {
+                let response = FundingResponse {
+                    outpoints: (index..index + fund_request.no_of_outpoints)
+                        .map(|index| OutPoint {
+                            hash: tx.hash(),
+                            index,
+                        })
+                        .collect(),
+                    txs: vec![tx.clone()],
+                    broadcasts: record.iter().cloned().collect(),
+                };
+                index += fund_request.no_of_outpoints;
+                results[*i] = Some(if broadcast_ok {
+                    self.funding_created(fund_request, &response);
+                    Ok(response)
+                } else {
+                    Err(response.broadcast_error())
+                });
+            }
+        }
+
+        // Fund the remaining requests separately
+        for (fund_request, result) in fund_requests.iter().zip(results.iter_mut()) {
+            if result.is_some() {
+                continue;
+            }
+            let client_id = &fund_request.client_id;
+            *result = Some(if !self.is_client_id_valid(client_id) {
+                Err(ApiError::unknown_client(client_id))
+            } else if self.is_client_watch_only(client_id) {
+                Err(ApiError::watch_only_client(client_id))
+            } else if self.has_sufficent_balance(fund_request) != Some(true) {
+                Err(ApiError::insufficient_funds(client_id))
+            } else {
+                self.create_funding_outpoints(fund_request).await
+            });
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    /// Track the funding txs and publish the funding created event
+    fn funding_created(&mut self, fund_request: &FundRequest, response: &FundingResponse) {
+        self.track_funding_txs(&fund_request.client_id, &response.txs);
+        self.events.publish(ServiceEvent::FundingCreated {
+            client_id: fund_request.client_id.clone(),
+            txids: response.txs.iter().map(|tx| tx.hash().encode()).collect(),
+            satoshi: fund_request.satoshi,
+            no_of_outpoints: fund_request.no_of_outpoints,
+        });
+    }
+
+    fn publish_broadcast_failed(&self, client_id: &str, tx: &Tx) {
+        self.events.publish(ServiceEvent::BroadcastFailed {
+            client_id: Some(client_id.to_string()),
+            txid: tx.hash().encode(),
+            reason: "Failed to broadcast funding transaction.".to_string(),
+        });
+    }
+
     /// Create funding outpoints based on the provided arguments
     pub async fn create_funding_outpoints(
         &mut self,
         fund_request: &FundRequest,
-    ) -> Result<FundingResponse, String> {
+    ) -> Result<FundingResponse, ApiError> {
         let client: &mut Client = self
             .clients
             .iter_mut()
             .find(|x| x.client_id == fund_request.client_id)
-            .unwrap();
+            .ok_or_else(|| ApiError::unknown_client(&fund_request.client_id))?;
+        if client.is_watch_only() {
+            return Err(ApiError::watch_only_client(&fund_request.client_id));
+        }
 
         let mut response = FundingResponse::default();
         // Check balance
@@ -247,47 +790,349 @@ impl Service {
                 let tx_as_str = tx_as_hexstr(a_tx);
                 log::info!("tx_as_str = {}", &tx_as_str);
 
-                match self.blockchain_interface.broadcast_tx(a_tx).await {
-                    Ok(_hash) => {
-                        // Append to the list
-                        // Note the provided hash is a str whereas OutPoint wants a Hash256
-                        response.outpoints.push(OutPoint {
-                            hash: a_tx.hash(),
-                            index: 1,
-                        });
-                    }
-                    _ => {
-                        log::info!("Failed to broadcast funding transaction");
-                        return Err(
-                            "{\"description\": \"Failed to broadcast funding transaction.\"}"
-                                .to_string(),
-                        );
-                    }
+                let (broadcast_ok, record) = self.broadcast_tx(a_tx).await;
+                response.broadcasts.extend(record);
+                if broadcast_ok {
+                    // Append to the list
+                    response.outpoints.push(OutPoint {
+                        hash: a_tx.hash(),
+                        index: 1,
+                    });
+                } else {
+                    log::info!("Failed to broadcast funding transaction");
+                    let broadcast = &response.txs[..response.outpoints.len()];
+                    self.track_funding_txs(&fund_request.client_id, broadcast);
+                    self.publish_broadcast_failed(&fund_request.client_id, a_tx);
+                    return Err(response.broadcast_error());
                 }
             }
+            self.funding_created(fund_request, &response);
             // Provide all the outpoints
             Ok(response)
         } else {
             // Create one tx
-            let b_tx: Tx = client.create_funding_tx(fund_request).unwrap();
+            let b_tx: Tx = client
+                .create_funding_tx(fund_request)
+                .ok_or_else(|| ApiError::insufficient_funds(&fund_request.client_id))?;
             // broadcast tx
             //let tx_as_str = tx_as_hexstr(&b_tx);
             response.txs.push(b_tx.clone());
 
-            match self.blockchain_interface.broadcast_tx(&b_tx).await {
-                Ok(_hash) => {
-                    let hash = b_tx.hash();
-                    response.outpoints = self.get_outpoints(hash, fund_request.no_of_outpoints);
-                    Ok(response)
-                }
-                _ => {
-                    log::info!("Failed to broadcast funding transaction");
-                    Err(
-                        "{\"description\": \"Failed to broadcast funding transaction.\"}"
-                            .to_string(),
-                    )
-                }
+            let (broadcast_ok, record) = self.broadcast_tx(&b_tx).await;
+            response.broadcasts.extend(record);
+            if broadcast_ok {
+                let hash = b_tx.hash();
+                response.outpoints = self.get_outpoints(hash, fund_request.no_of_outpoints);
+                self.funding_created(fund_request, &response);
+                Ok(response)
+            } else {
+                log::info!("Failed to broadcast funding transaction");
+                self.publish_broadcast_failed(&fund_request.client_id, &b_tx);
+                Err(response.broadcast_error())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BlockchainBackendConfig, SimulatedFundingConfig};
+
+    const ADDRESS: &str = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+
+    fn simulated_config() -> Config {
+        let mut config = Config::default();
+        config.blockchain_interface.interface_type = "simulated".to_string();
+        config.blockchain_interface.network_type = "testnet".to_string();
+        config
+    }
+
+    /// Return a service with client id1, whose address is funded with the unspents on the simulated chain
+    async fn funded_service(unspents: &[i64]) -> Service {
+        let mut config = simulated_config();
+        config.simulated.funding = unspents
+            .iter()
+            .map(|satoshi| SimulatedFundingConfig {
+                address: ADDRESS.to_string(),
+                satoshi: *satoshi,
+            })
+            .collect();
+        config.client = Some(vec![ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        }]);
+        Service::new(&config).await
+    }
+
+    fn fund_request(client_id: &str, satoshi: u64, no_of_outpoints: u32) -> FundRequest {
+        FundRequest {
+            client_id: client_id.to_string(),
+            satoshi,
+            no_of_outpoints,
+            multiple_tx: false,
+            locking_script: hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac")
+                .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_combined() {
+        let mut service = funded_service(&[100_000]).await;
+        let requests = [fund_request("id1", 123, 1), fund_request("id1", 456, 2)];
+        let results = service.create_batch_funding_outpoints(&requests).await;
+        let responses: Vec<&FundingResponse> = results
+            .iter()
+            .map(|result| result.as_ref().unwrap())
+            .collect();
+
+        // The requests share one tx, the change is output 0 followed by each request's outpoints
+        let tx = &responses[0].txs[0];
+        assert_eq!(responses[1].txs.len(), 1);
+        assert_eq!(responses[1].txs[0].hash(), tx.hash());
+        let indices = |response: &FundingResponse| -> Vec<u32> {
+            response
+                .outpoints
+                .iter()
+                .map(|outpoint| outpoint.index)
+                .collect()
+        };
+        assert_eq!(indices(responses[0]), vec![1]);
+        assert_eq!(indices(responses[1]), vec![2, 3]);
+        let satoshis: Vec<i64> = tx.outputs.iter().map(|output| output.satoshis).collect();
+        assert_eq!(satoshis[1..], [123, 456, 456]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_fallback() {
+        // Neither unspent can fund the combined tx, but each can fund one request
+        let mut service = funded_service(&[2_000, 2_000]).await;
+        let requests = [fund_request("id1", 700, 1), fund_request("id1", 700, 1)];
+        let results = service.create_batch_funding_outpoints(&requests).await;
+        let responses: Vec<&FundingResponse> = results
+            .iter()
+            .map(|result| result.as_ref().unwrap())
+            .collect();
+
+        assert_ne!(responses[0].txs[0].hash(), responses[1].txs[0].hash());
+        for response in responses {
+            assert_eq!(response.txs.len(), 1);
+            assert_eq!(response.outpoints[0].index, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_unknown_client() {
+        let mut service = Service::new(&simulated_config()).await;
+        let requests = [
+            fund_request("unknown", 123, 1),
+            fund_request("unknown", 456, 1),
+        ];
+        let results = service.create_batch_funding_outpoints(&requests).await;
+        assert_eq!(results.len(), 2);
+        for result in results {
+            assert!(matches!(result, Err(e) if e.code == ErrorCode::UnknownClient));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_key_hash() {
+        let api_key_hash = crate::auth::hash_token("client-key");
+        let mut config = simulated_config();
+        config.client = Some(
+            [
+                ("id1", api_key_hash.to_uppercase()),
+                ("id2", api_key_hash.clone()),
+                ("id3", "1234".to_string()),
+            ]
+            .into_iter()
+            .map(|(client_id, api_key_hash)| ClientConfig {
+                client_id: client_id.to_string(),
+                address: Some(ADDRESS.to_string()),
+                api_key_hash: Some(api_key_hash),
+                ..Default::default()
+            })
+            .collect(),
+        );
+        let mut service = Service::new(&config).await;
+
+        // The hash is lowercased, duplicate and invalid hashes are quarantined
+        assert_eq!(
+            service
+                .get_api_key_client(&api_key_hash)
+                .map(String::as_str),
+            Some("id1")
+        );
+        assert!(service.is_client_quarantined("id2"));
+        assert!(service.is_client_quarantined("id3"));
+
+        let client_config = ClientConfig {
+            client_id: "id4".to_string(),
+            address: Some(ADDRESS.to_string()),
+            api_key_hash: Some(api_key_hash.clone()),
+            ..Default::default()
+        };
+        assert!(service.add_client(&client_config).is_err());
+        assert!(!service.is_client_id_valid("id4"));
+    }
+
+    #[tokio::test]
+    async fn test_update_tx_status() {
+        let mut service = Service::new(&simulated_config()).await;
+        let mut events = service.subscribe_events();
+        let callback = |body: &str| -> ArcTxResponse { serde_json::from_str(body).unwrap() };
+
+        // Callbacks without a txStatus, or with a status that is not final, are ignored
+        service.update_tx_status(&callback(r#"{"txid": "1234"}"#));
+        service.update_tx_status(&callback(
+            r#"{"txid": "1234", "txStatus": "SEEN_ON_NETWORK"}"#,
+        ));
+        assert!(events.try_recv().is_err());
+
+        service.update_tx_status(&callback(
+            r#"{"txid": "1234", "txStatus": "REJECTED", "extraInfo": "bad tx"}"#,
+        ));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ServiceEvent::BroadcastFailed { reason, .. }) if reason == "bad tx"
+        ));
+        service.update_tx_status(&callback(
+            r#"{"txid": "5678", "txStatus": "MINED", "blockHeight": 10}"#,
+        ));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ServiceEvent::TxConfirmed {
+                block_height: Some(10),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_client() {
+        let wif_key = "cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG";
+        let filename =
+            std::env::temp_dir().join(format!("delete_client_{}.toml", std::process::id()));
+        let mut config = simulated_config();
+        config.dynamic_config.filename = filename.to_string_lossy().to_string();
+        let mut service = Service::new(&config).await;
+        let mut events = service.subscribe_events();
+
+        let client_config = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some(wif_key.into()),
+            api_key_hash: Some(crate::auth::hash_token("client-key")),
+            ..Default::default()
+        };
+        service.add_client(&client_config).unwrap();
+        assert!(std::fs::read_to_string(&filename)
+            .unwrap()
+            .contains(wif_key));
+
+        // The client, its API key and its key in the dynamic config are removed
+        service.delete_client("id1");
+        assert!(!service.is_client_id_valid("id1"));
+        assert_eq!(service.get_address("id1"), None);
+        assert!(service
+            .get_api_key_client(&crate::auth::hash_token("client-key"))
+            .is_none());
+        assert!(!std::fs::read_to_string(&filename)
+            .unwrap()
+            .contains(wif_key));
+        assert!(matches!(
+            events.recv().await,
+            Ok(ServiceEvent::ClientAdded { client_id }) if client_id == "id1"
+        ));
+        assert!(matches!(
+            events.recv().await,
+            Ok(ServiceEvent::ClientRemoved { client_id }) if client_id == "id1"
+        ));
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_single_request() {
+        let mut config = simulated_config();
+        config.simulated.funding = vec![SimulatedFundingConfig {
+            address: ADDRESS.to_string(),
+            satoshi: 100_000,
+        }];
+        config.client = Some(vec![ClientConfig {
+            client_id: "watch".to_string(),
+            address: Some(ADDRESS.to_string()),
+            ..Default::default()
+        }]);
+        let mut service = Service::new(&config).await;
+        assert_eq!(
+            service.has_sufficent_balance(&fund_request("watch", 123, 1)),
+            Some(true)
+        );
+
+        // Single requests are funded separately, so are checked in the same way as /fund
+        let requests = [
+            fund_request("unknown", 123, 1),
+            fund_request("watch", 123, 1),
+        ];
+        let results = service.create_batch_funding_outpoints(&requests).await;
+        assert!(matches!(&results[0], Err(e) if e.code == ErrorCode::UnknownClient));
+        assert!(matches!(&results[1], Err(e) if e.code == ErrorCode::WatchOnlyClient));
+    }
+
+    #[tokio::test]
+    async fn test_failover_hung_primary() {
+        // The hung primary accepts connections but never responds
+        let hung = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = simulated_config();
+        config.circuit_breaker.query_timeout = 1;
+        config.blockchain_interface.interface_type = "failover".to_string();
+        config.blockchain_interface.backend = Some(vec![
+            BlockchainBackendConfig {
+                interface_type: "rpc".to_string(),
+                url: Some(format!("http://{}", hung.local_addr().unwrap())),
+                ..Default::default()
+            },
+            BlockchainBackendConfig {
+                interface_type: "simulated".to_string(),
+                url: None,
+                ..Default::default()
+            },
+        ]);
+
+        // The primary times out and the service connects using the secondary
+        let service = Service::new(&config).await;
+        assert!(!service.is_blockchain_failed());
+        let status = service.get_status();
+        assert_eq!(status.active_backend, "simulated");
+        assert_eq!(status.circuit_breaker, CircuitState::Closed);
+    }
+
+    fn tracked_tx(txid: &str, parents: &[&str]) -> TrackedTx {
+        TrackedTx {
+            txid: txid.to_string(),
+            client_id: "client1".to_string(),
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_confirmed_txs() {
+        // tx2 spends the change of tx1, which spends the change of tx0, and tx3 is unrelated
+        let txs = [
+            tracked_tx("tx0", &["funding"]),
+            tracked_tx("tx1", &["tx0"]),
+            tracked_tx("tx2", &["tx1"]),
+            tracked_tx("tx3", &["other"]),
+        ];
+        let tracked: Vec<&TrackedTx> = txs.iter().collect();
+        assert!(confirmed_txs(&tracked, |_| None).is_empty());
+
+        // Only the change of tx2 is unspent, so its ancestors are confirmed by it
+        let confirmed = confirmed_txs(&tracked, |txid| (txid == "tx2").then_some(100));
+        assert_eq!(confirmed.len(), 3);
+        assert_eq!(confirmed["tx2"], Some(100));
+        assert_eq!(confirmed["tx1"], None);
+        assert_eq!(confirmed["tx0"], None);
+        assert!(!confirmed.contains_key("tx3"));
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chain_gang::{
+    address::{addr_encode, AddressType},
+    interface::{Balance, BlockchainInterface, Utxo, UtxoEntry},
+    messages::{Tx, TxOut},
+    network::Network,
+    script::Script,
+    util::{Error, Hash160, Result},
+};
+
+use crate::{config::SimulatedConfig, util::address_to_locking_script};
+
+/// An unspent output in the simulated chain
+#[derive(Debug, Clone)]
+struct Unspent {
+    address: Option<String>,
+    value: i64,
+    /// Height of the block containing the output, 0 if in the mempool
+    height: u32,
+}
+
+/// State of the simulated chain
+#[derive(Default)]
+struct ChainState {
+    height: u32,
+    /// Unspent outputs, keyed by (tx_hash, index)
+    unspent: HashMap<(String, u32), Unspent>,
+    /// Hashes of txs waiting to be mined
+    mempool: Vec<String>,
+    txs: HashMap<String, Tx>,
+}
+
+/// Simulated blockchain interface, for end-to-end testing
+/// Broadcast txs are validated against the UTXO set and placed in the mempool,
+/// blocks are mined on demand, periodically or as each tx is broadcast.
+/// Note that the unlocking scripts are not verified.
+#[derive(Clone)]
+pub struct SimulatedInterface {
+    network: Network,
+    state: Arc<Mutex<ChainState>>,
+    /// If set each broadcast tx is mined as it is accepted
+    mine_on_broadcast: bool,
+}
+
+/// Return the P2PKH address of the locking script, if it is a P2PKH locking script
+fn script_to_address(script: &Script, network: Network) -> Option<String> {
+    let bytes = &script.0;
+    // OP_DUP OP_HASH160 <hash160> OP_EQUALVERIFY OP_CHECKSIG
+    if bytes.len() == 25 && bytes[0..3] == [0x76, 0xa9, 0x14] && bytes[23..25] == [0x88, 0xac] {
+        let mut hash160 = Hash160([0; 20]);
+        hash160.0.copy_from_slice(&bytes[3..23]);
+        Some(addr_encode(&hash160, AddressType::P2PKH, network))
+    } else {
+        None
+    }
+}
+
+impl SimulatedInterface {
+    pub fn new(network: Network) -> Self {
+        SimulatedInterface {
+            network,
+            state: Arc::new(Mutex::new(ChainState::default())),
+            mine_on_broadcast: false,
+        }
+    }
+
+    /// Create a simulated chain from the config, funding the configured addresses
+    /// and starting the periodic mining if a block_period is configured
+    pub fn from_config(network: Network, simulated_config: &SimulatedConfig) -> Self {
+        let mut interface = SimulatedInterface::new(network);
+        interface.mine_on_broadcast = simulated_config.mine_on_broadcast;
+        for funding in &simulated_config.funding {
+            interface
+                .fund_address(&funding.address, funding.satoshi)
+                .unwrap_or_else(|e| panic!("Config simulated funding {}", e));
+        }
+        interface.mine_block();
+
+        if let Some(block_period) = simulated_config.block_period {
+            let miner = interface.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(block_period));
+                loop {
+                    interval.tick().await;
+                    miner.mine_block();
+                }
+            });
+        }
+        interface
+    }
+
+    /// Add an output of satoshi to the address in the mempool, using a coinbase tx
+    pub fn fund_address(&self, address: &str, satoshi: i64) -> std::result::Result<(), String> {
+        let locking_script = address_to_locking_script(address, self.network)?;
+        let mut state = self.state.lock().unwrap();
+        let mut lock_script = Script::new();
+        lock_script.append_slice(&locking_script);
+        let tx = Tx {
+            version: 1,
+            inputs: Vec::new(),
+            outputs: vec![TxOut {
+                satoshis: satoshi,
+                lock_script,
+            }],
+            // Ensure each coinbase tx has a unique hash
+            lock_time: state.txs.len() as u32,
+        };
+        let tx_hash = tx.hash().encode();
+        state.unspent.insert(
+            (tx_hash.clone(), 0),
+            Unspent {
+                address: Some(address.to_string()),
+                value: satoshi,
+                height: 0,
+            },
+        );
+        state.mempool.push(tx_hash.clone());
+        state.txs.insert(tx_hash, tx);
+        Ok(())
+    }
+
+    /// Mine a block containing all the txs in the mempool, returns the new height
+    pub fn mine_block(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.height += 1;
+        let height = state.height;
+        let mempool = std::mem::take(&mut state.mempool);
+        for unspent in state
+            .unspent
+            .iter_mut()
+            .filter_map(|(key, unspent)| mempool.contains(&key.0).then_some(unspent))
+        {
+            unspent.height = height;
+        }
+        log::debug!(
+            "simulated block {} mined with {} txs",
+            height,
+            mempool.len()
+        );
+        height
+    }
+
+    /// Return the number of txs in the mempool
+    pub fn mempool_size(&self) -> usize {
+        self.state.lock().unwrap().mempool.len()
+    }
+
+    /// Return the unspent outputs of the address
+    fn address_unspent(&self, address: &str) -> Vec<((String, u32), Unspent)> {
+        let state = self.state.lock().unwrap();
+        state
+            .unspent
+            .iter()
+            .filter(|(_, unspent)| unspent.address.as_deref() == Some(address))
+            .map(|(key, unspent)| (key.clone(), unspent.clone()))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BlockchainInterface for SimulatedInterface {
+    fn set_network(&mut self, network: &Network) {
+        self.network = *network;
+    }
+
+    async fn status(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_balance(&self, address: &str) -> Result<Balance> {
+        let mut balance = Balance::default();
+        for (_, unspent) in self.address_unspent(address) {
+            if unspent.height > 0 {
+                balance.confirmed += unspent.value as u64;
+            } else {
+                balance.unconfirmed += unspent.value as u64;
+            }
+        }
+        Ok(balance)
+    }
+
+    async fn get_utxo(&self, address: &str) -> Result<Utxo> {
+        Ok(self
+            .address_unspent(address)
+            .into_iter()
+            .map(|((tx_hash, tx_pos), unspent)| UtxoEntry {
+                height: unspent.height,
+                tx_pos,
+                tx_hash,
+                value: unspent.value,
+            })
+            .collect())
+    }
+
+    async fn broadcast_tx(&self, tx: &Tx) -> Result<String> {
+        let mut state = self.state.lock().unwrap();
+        let tx_hash = tx.hash().encode();
+        if state.txs.contains_key(&tx_hash) {
+            return Err(Error::BadData(format!("tx {} already known", tx_hash)));
+        }
+
+        // Validate inputs against the UTXO set
+        let mut input_value: i64 = 0;
+        for (i, input) in tx.inputs.iter().enumerate() {
+            let key = (input.prev_output.hash.encode(), input.prev_output.index);
+            if tx.inputs[..i].iter().any(|x| {
+                x.prev_output.hash == input.prev_output.hash
+                    && x.prev_output.index == input.prev_output.index
+            }) {
+                return Err(Error::BadData(format!("input {:?} spent twice", key)));
+            }
+            match state.unspent.get(&key) {
+                Some(unspent) => input_value += unspent.value,
+                None => return Err(Error::BadData(format!("input {:?} is not unspent", key))),
+            }
+        }
+        let output_value: i64 = tx.outputs.iter().map(|x| x.satoshis).sum();
+        if tx.inputs.is_empty() || output_value > input_value {
+            return Err(Error::BadData(format!(
+                "tx {} outputs {} exceed inputs {}",
+                tx_hash, output_value, input_value
+            )));
+        }
+
+        // Update the UTXO set
+        for input in &tx.inputs {
+            state
+                .unspent
+                .remove(&(input.prev_output.hash.encode(), input.prev_output.index));
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            state.unspent.insert(
+                (tx_hash.clone(), index as u32),
+                Unspent {
+                    address: script_to_address(&output.lock_script, self.network),
+                    value: output.satoshis,
+                    height: 0,
+                },
+            );
+        }
+        state.mempool.push(tx_hash.clone());
+        state.txs.insert(tx_hash.clone(), tx.clone());
+        drop(state);
+        if self.mine_on_broadcast {
+            self.mine_block();
+        }
+        Ok(tx_hash)
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<Tx> {
+        let state = self.state.lock().unwrap();
+        state
+            .txs
+            .get(txid)
+            .cloned()
+            .ok_or_else(|| Error::BadData(format!("tx {} not found", txid)))
+    }
+
+    async fn get_block_headers(&self) -> Result<String> {
+        let height = self.state.lock().unwrap().height;
+        Ok(format!(
+            "{{\"height\": {}, \"mempool_size\": {}}}",
+            height,
+            self.mempool_size()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::Client, client::FundRequest, config::ClientConfig};
+
+    #[tokio::test]
+    async fn test_simulated_funding() {
+        let network = Network::BSV_Testnet;
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+        let interface = SimulatedInterface::new(network);
+        interface.fund_address(address, 100_000).unwrap();
+        interface.mine_block();
+
+        let client_config = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        let mut client = Client::new(&client_config, network).unwrap();
+        client.update_balance(&interface).await.unwrap();
+        assert_eq!(client.get_balance().confirmed, 100_000);
+
+        let fund_request = FundRequest {
+            client_id: "id1".to_string(),
+            satoshi: 123,
+            no_of_outpoints: 2,
+            multiple_tx: false,
+            locking_script: hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac")
+                .unwrap(),
+        };
+        let tx = client.create_funding_tx(&fund_request).unwrap();
+        interface.broadcast_tx(&tx).await.unwrap();
+        assert_eq!(interface.mempool_size(), 1);
+
+        // Double spend is rejected
+        assert!(interface.broadcast_tx(&tx).await.is_err());
+
+        // Change and funding outputs are to the same address
+        let balance = interface.get_balance(address).await.unwrap();
+        assert_eq!(balance.confirmed, 0);
+        assert_eq!(balance.unconfirmed, 100_000 - 750);
+
+        interface.mine_block();
+        assert_eq!(interface.mempool_size(), 0);
+        let balance = interface.get_balance(address).await.unwrap();
+        assert_eq!(balance.confirmed, 100_000 - 750);
+        assert_eq!(interface.get_utxo(address).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_mine_on_broadcast() {
+        let network = Network::BSV_Testnet;
+        let address = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+        let simulated_config = SimulatedConfig {
+            mine_on_broadcast: true,
+            funding: vec![crate::config::SimulatedFundingConfig {
+                address: address.to_string(),
+                satoshi: 100_000,
+            }],
+            ..Default::default()
+        };
+        let interface = SimulatedInterface::from_config(network, &simulated_config);
+
+        let client_config = ClientConfig {
+            client_id: "id1".to_string(),
+            wif_key: Some("cW1ciwAgTLs2EGa6cZHpfLZmUzXbkvq72s15rbiUonkrQAhDU4FG".into()),
+            ..Default::default()
+        };
+        let mut client = Client::new(&client_config, network).unwrap();
+        client.update_balance(&interface).await.unwrap();
+        let fund_request = FundRequest {
+            client_id: "id1".to_string(),
+            satoshi: 123,
+            no_of_outpoints: 1,
+            multiple_tx: false,
+            locking_script: hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac")
+                .unwrap(),
+        };
+        let tx = client.create_funding_tx(&fund_request).unwrap();
+        interface.broadcast_tx(&tx).await.unwrap();
+
+        // The tx is mined as it is broadcast
+        assert_eq!(interface.mempool_size(), 0);
+        let balance = interface.get_balance(address).await.unwrap();
+        assert_eq!(balance.confirmed, 100_000 - 750);
+        assert_eq!(balance.unconfirmed, 0);
+    }
+}
@@ -0,0 +1,338 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::web;
+use chain_gang::network::Network;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::{events::ServiceEvent, rest_api::AppState, util::address_to_locking_script};
+
+/// Event received from the subscription feed
+#[derive(Debug, PartialEq, Eq)]
+pub enum SubscriptionEvent {
+    /// Activity on the given addresses
+    Address(Vec<String>),
+    /// A new block
+    Block,
+}
+
+const SCRIPTHASH_SUBSCRIBE: &str = "blockchain.scripthash.subscribe";
+const HEADERS_SUBSCRIBE: &str = "blockchain.headers.subscribe";
+
+/// Return the Electrum script hash of the address,
+/// the SHA-256 hash of its locking script in reverse byte order as hex
+pub fn address_to_scripthash(address: &str, network: Network) -> Result<String, String> {
+    let locking_script = address_to_locking_script(address, network)?;
+    let mut hash = Sha256::digest(&locking_script).to_vec();
+    hash.reverse();
+    Ok(hex::encode(hash))
+}
+
+/// Return the Electrum JSON-RPC subscribe request
+fn subscribe_request(id: u64, method: &str, params: Value) -> Message {
+    Message::text(
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}).to_string(),
+    )
+}
+
+/// Parse an Electrum notification, returns None if not a recognised event
+/// Script hash notifications - {"method": "blockchain.scripthash.subscribe", "params": [scripthash, status]}
+/// Header notifications - {"method": "blockchain.headers.subscribe", "params": [{"height": .., "hex": ..}]}
+/// The responses to the subscribe requests are ignored
+pub fn parse_event(
+    message: &str,
+    scripthashes: &HashMap<String, String>,
+) -> Option<SubscriptionEvent> {
+    let notification: Value = serde_json::from_str(message).ok()?;
+    match notification["method"].as_str()? {
+        SCRIPTHASH_SUBSCRIBE => {
+            let scripthash = notification["params"][0].as_str()?;
+            let address = scripthashes.get(scripthash)?;
+            Some(SubscriptionEvent::Address(vec![address.clone()]))
+        }
+        HEADERS_SUBSCRIBE => Some(SubscriptionEvent::Block),
+        _ => None,
+    }
+}
+
+/// Subscriptions made on a feed connection, mapping script hashes to addresses
+struct Subscriptions {
+    network: Network,
+    scripthashes: HashMap<String, String>,
+    next_id: u64,
+}
+
+impl Subscriptions {
+    /// Return the subscribe request for the address, if it is not already subscribed
+    fn subscribe(&mut self, address: &str) -> Option<Message> {
+        let scripthash = match address_to_scripthash(address, self.network) {
+            Ok(scripthash) => scripthash,
+            Err(e) => {
+                log::warn!("subscription unable to subscribe {}", e);
+                return None;
+            }
+        };
+        if self.scripthashes.contains_key(&scripthash) {
+            return None;
+        }
+        self.scripthashes
+            .insert(scripthash.clone(), address.to_string());
+        self.next_id += 1;
+        Some(subscribe_request(
+            self.next_id,
+            SCRIPTHASH_SUBSCRIBE,
+            json!([scripthash]),
+        ))
+    }
+}
+
+/// Connect to the feed, subscribe to block headers and the addresses,
+/// and send the events received until the connection closes.
+/// Addresses received on new_addresses, while connected, are also subscribed to
+async fn read_events(
+    url: &str,
+    addresses: &[String],
+    network: Network,
+    new_addresses: &mut mpsc::UnboundedReceiver<String>,
+    sender: &mpsc::UnboundedSender<SubscriptionEvent>,
+) -> Result<(), String> {
+    let (mut stream, _) = connect_async(url).await.map_err(|e| e.to_string())?;
+    log::info!("subscription connected to {}", url);
+
+    let mut subscriptions = Subscriptions {
+        network,
+        scripthashes: HashMap::new(),
+        next_id: 0,
+    };
+    let mut requests = vec![subscribe_request(0, HEADERS_SUBSCRIBE, json!([]))];
+    requests.extend(
+        addresses
+            .iter()
+            .filter_map(|address| subscriptions.subscribe(address)),
+    );
+    for request in requests {
+        stream.send(request).await.map_err(|e| e.to_string())?;
+    }
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                let message = match message {
+                    Some(message) => message.map_err(|e| e.to_string())?,
+                    None => break,
+                };
+                match message {
+                    Message::Text(text) => {
+                        match parse_event(text.as_str(), &subscriptions.scripthashes) {
+                            Some(event) => sender.send(event).map_err(|e| e.to_string())?,
+                            None => log::debug!("subscription ignored message {}", text.as_str()),
+                        }
+                    }
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+            Some(address) = new_addresses.recv() => {
+                if let Some(request) = subscriptions.subscribe(&address) {
+                    stream.send(request).await.map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Listen for events on the feed, reconnecting and resubscribing to the clients' addresses if the connection fails
+async fn listen(
+    url: String,
+    reconnect_period: Duration,
+    data: web::Data<AppState>,
+    mut new_addresses: mpsc::UnboundedReceiver<String>,
+    sender: mpsc::UnboundedSender<SubscriptionEvent>,
+) {
+    loop {
+        let (addresses, network) = {
+            let service = data.service.lock().await;
+            (service.get_addresses(), service.get_network())
+        };
+        match read_events(&url, &addresses, network, &mut new_addresses, &sender).await {
+            Ok(()) => log::warn!("subscription to {} closed", url),
+            Err(e) => log::warn!("subscription to {} failed {}", url, e),
+        }
+        if sender.is_closed() {
+            return;
+        }
+        tokio::time::sleep(reconnect_period).await;
+    }
+}
+
+/// Send the addresses of the clients added to the service
+async fn added_addresses(data: web::Data<AppState>, sender: mpsc::UnboundedSender<String>) {
+    let mut events = data.service.lock().await.subscribe_events();
+    loop {
+        match events.recv().await {
+            Ok(ServiceEvent::ClientAdded { client_id }) => {
+                let address = data.service.lock().await.get_address(&client_id);
+                if let Some(address) = address {
+                    if sender.send(address).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Subscribe to the Electrum protocol feed and refresh the clients affected by each event
+/// Note that the periodic refresh continues as a fallback
+pub async fn run_subscription(url: String, reconnect_period: Duration, data: web::Data<AppState>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let (address_sender, address_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(added_addresses(data.clone(), address_sender));
+    tokio::spawn(listen(
+        url,
+        reconnect_period,
+        data.clone(),
+        address_receiver,
+        sender,
+    ));
+
+    while let Some(event) = receiver.recv().await {
+        let mut service = data.service.lock().await;
+        match event {
+            SubscriptionEvent::Address(addresses) => {
+                service.update_client_balances(&addresses).await
+            }
+            SubscriptionEvent::Block => service.update_unconfirmed_balances().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    const ADDRESS: &str = "mwxrVFsJps3sxz5A38Mbrze8kPKq7D5NxF";
+
+    #[test]
+    fn test_parse_event() {
+        let scripthash = address_to_scripthash(ADDRESS, Network::BSV_Testnet).unwrap();
+        let scripthashes = HashMap::from([(scripthash.clone(), ADDRESS.to_string())]);
+        assert_eq!(
+            parse_event(
+                &json!({"jsonrpc": "2.0", "method": SCRIPTHASH_SUBSCRIBE, "params": [scripthash, "abcd"]})
+                    .to_string(),
+                &scripthashes
+            ),
+            Some(SubscriptionEvent::Address(vec![ADDRESS.to_string()]))
+        );
+        assert_eq!(
+            parse_event(
+                r#"{"jsonrpc": "2.0", "method": "blockchain.headers.subscribe", "params": [{"height": 1, "hex": "00"}]}"#,
+                &scripthashes
+            ),
+            Some(SubscriptionEvent::Block)
+        );
+        // Responses and unknown script hashes are ignored
+        assert_eq!(
+            parse_event(
+                r#"{"jsonrpc": "2.0", "id": 1, "result": null}"#,
+                &scripthashes
+            ),
+            None
+        );
+        assert_eq!(
+            parse_event(
+                r#"{"jsonrpc": "2.0", "method": "blockchain.scripthash.subscribe", "params": ["00", "abcd"]}"#,
+                &scripthashes
+            ),
+            None
+        );
+        assert_eq!(parse_event("not json", &scripthashes), None);
+    }
+
+    #[test]
+    fn test_address_to_scripthash() {
+        // The locking script of the address is 76a914b467faf0ef536db106d67f872c448bcaccb878c988ac
+        let mut expected = Sha256::digest(
+            hex::decode("76a914b467faf0ef536db106d67f872c448bcaccb878c988ac").unwrap(),
+        )
+        .to_vec();
+        expected.reverse();
+        assert_eq!(
+            address_to_scripthash(ADDRESS, Network::BSV_Testnet).unwrap(),
+            hex::encode(expected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_events() {
+        let scripthash = address_to_scripthash(ADDRESS, Network::BSV_Testnet).unwrap();
+        // Local Electrum server stand-in
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server_scripthash = scripthash.clone();
+        let server = tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(tcp_stream).await.unwrap();
+            // The headers and address subscribe requests
+            let mut methods = Vec::new();
+            for _ in 0..2 {
+                let request = ws_stream.next().await.unwrap().unwrap();
+                let request: Value = serde_json::from_str(request.to_text().unwrap()).unwrap();
+                ws_stream
+                    .send(Message::text(
+                        json!({"jsonrpc": "2.0", "id": request["id"], "result": null}).to_string(),
+                    ))
+                    .await
+                    .unwrap();
+                methods.push((
+                    request["method"].as_str().unwrap().to_string(),
+                    request["params"].clone(),
+                ));
+            }
+            for message in [
+                json!({"jsonrpc": "2.0", "method": SCRIPTHASH_SUBSCRIBE, "params": [server_scripthash, "abcd"]}),
+                json!({"jsonrpc": "2.0", "method": HEADERS_SUBSCRIBE, "params": [{"height": 2, "hex": "00"}]}),
+            ] {
+                ws_stream
+                    .send(Message::text(message.to_string()))
+                    .await
+                    .unwrap();
+            }
+            ws_stream.close(None).await.unwrap();
+            methods
+        });
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let (_address_sender, mut new_addresses) = mpsc::unbounded_channel();
+        read_events(
+            &url,
+            &[ADDRESS.to_string()],
+            Network::BSV_Testnet,
+            &mut new_addresses,
+            &sender,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            server.await.unwrap(),
+            vec![
+                (HEADERS_SUBSCRIBE.to_string(), json!([])),
+                (SCRIPTHASH_SUBSCRIBE.to_string(), json!([scripthash])),
+            ]
+        );
+        assert_eq!(
+            receiver.recv().await,
+            Some(SubscriptionEvent::Address(vec![ADDRESS.to_string()]))
+        );
+        assert_eq!(receiver.recv().await, Some(SubscriptionEvent::Block));
+    }
+}
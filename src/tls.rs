@@ -0,0 +1,106 @@
+use std::{any::Any, fs::File, io::BufReader, sync::Arc};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+
+use crate::config::TlsConfig;
+
+/// Client certificate presented on a mutual TLS connection
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// Common name of the certificate's subject
+    pub common_name: String,
+}
+
+fn load_certs(filename: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(filename).map_err(|e| format!("Unable to open {} - {}", filename, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Unable to read certificates from {} - {}", filename, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", filename));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(filename: &str) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(filename).map_err(|e| format!("Unable to open {} - {}", filename, e))?;
+    // Only report the filename, as the error may contain key material
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("Unable to read private key from {}", filename))
+}
+
+/// Return the rustls server config for the TLS config
+/// If a client_ca_file is configured, client certificates are verified against it (mutual TLS)
+pub fn server_config(tls_config: &TlsConfig) -> Result<ServerConfig, String> {
+    let certs = load_certs(&tls_config.cert_file)?;
+    let key = load_private_key(&tls_config.key_file)?;
+
+    let builder = match &tls_config.client_ca_file {
+        Some(client_ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca_file)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid CA certificate in {} - {}", client_ca_file, e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if tls_config.client_certificate_required {
+                verifier.build()
+            } else {
+                verifier.allow_unauthenticated().build()
+            }
+            .map_err(|e| format!("Unable to create client certificate verifier - {}", e))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate or key - {}", e))
+}
+
+/// Return the common name of the certificate's subject
+fn common_name(cert: &CertificateDer) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let common_name = cert.subject().iter_common_name().next()?;
+    common_name.as_str().ok().map(|cn| cn.to_string())
+}
+
+/// Connection hook, records the client certificate of mutual TLS connections
+/// so that handlers can read it with `req.conn_data::<ClientCertificate>()`
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    if let Some(tls_stream) = connection.downcast_ref::<TlsStream<TcpStream>>() {
+        let (_, session) = tls_stream.get_ref();
+        if let Some(common_name) = session
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(common_name)
+        {
+            data.insert(ClientCertificate { common_name });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_certificate() {
+        let tls_config = TlsConfig {
+            cert_file: "data/missing-cert.pem".to_string(),
+            key_file: "data/missing-key.pem".to_string(),
+            ..Default::default()
+        };
+        let result = server_config(&tls_config);
+        assert!(result.unwrap_err().contains("data/missing-cert.pem"));
+    }
+}
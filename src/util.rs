@@ -1,7 +1,13 @@
 use chain_gang::{
+    address::{addr_decode, AddressType},
     messages::{Payload, Tx},
+    network::Network,
     util::Serializable,
 };
+use rand::{rngs::OsRng, RngCore};
+use zeroize::Zeroize;
+
+use crate::secret::SecretString;
 
 /// Convert a transaction into a hexstring
 pub fn tx_as_hexstr(tx: &Tx) -> String {
@@ -9,3 +15,75 @@ pub fn tx_as_hexstr(tx: &Tx) -> String {
     tx.write(&mut b).unwrap();
     hex::encode(&b)
 }
+
+/// Generate a new random private key for the given network, returned as a compressed WIF key
+pub fn generate_wif(network: Network) -> SecretString {
+    let prefix: u8 = match network {
+        Network::BSV_Mainnet => 0x80,
+        _ => 0xef,
+    };
+    let mut key_bytes = [0u8; 34];
+    key_bytes[0] = prefix;
+    OsRng.fill_bytes(&mut key_bytes[1..33]);
+    // Compressed public key flag
+    key_bytes[33] = 0x01;
+    let wif = bs58::encode(&key_bytes).with_check().into_string();
+    key_bytes.zeroize();
+    SecretString::from(wif)
+}
+
+/// Check that the address is valid for the given network
+pub fn check_address_network(address: &str, network: Network) -> Result<(), String> {
+    addr_decode(address, network).map(|_| ()).map_err(|_| {
+        format!(
+            r#"address = "{}" is not a valid {:?} address."#,
+            address, network
+        )
+    })
+}
+
+/// Convert a P2PKH address into its locking script, checking it is valid for the given network
+pub fn address_to_locking_script(address: &str, network: Network) -> Result<Vec<u8>, String> {
+    let (hash160, address_type) = addr_decode(address, network).map_err(|_| {
+        format!(
+            r#"address = "{}" is not a valid {:?} address."#,
+            address, network
+        )
+    })?;
+    if address_type != AddressType::P2PKH {
+        return Err(format!(
+            r#"address = "{}" is not a P2PKH address."#,
+            address
+        ));
+    }
+    // OP_DUP OP_HASH160 <hash160> OP_EQUALVERIFY OP_CHECKSIG
+    let mut locking_script: Vec<u8> = vec![0x76, 0xa9, 0x14];
+    locking_script.extend_from_slice(&hash160.0);
+    locking_script.extend_from_slice(&[0x88, 0xac]);
+    Ok(locking_script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_gang::wallet::Wallet;
+
+    #[test]
+    fn test_generate_wif() {
+        for (network, prefix) in [(Network::BSV_Mainnet, 0x80), (Network::BSV_Testnet, 0xef)] {
+            let wif = generate_wif(network);
+            let key_bytes = bs58::decode(wif.expose_secret())
+                .with_check(None)
+                .into_vec()
+                .unwrap();
+            assert_eq!(key_bytes.len(), 34);
+            assert_eq!(key_bytes[0], prefix);
+            assert_eq!(key_bytes[33], 0x01);
+
+            // The key loads as a wallet, whose address is for the network
+            let wallet = Wallet::from_wif(wif.expose_secret()).unwrap();
+            let address = wallet.get_address().unwrap();
+            assert!(check_address_network(&address, network).is_ok());
+        }
+    }
+}